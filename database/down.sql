@@ -1,3 +1,16 @@
+DROP TABLE IF EXISTS WebhookDeliveries;
+DROP TABLE IF EXISTS Webhooks;
+DROP TABLE IF EXISTS Consumables;
+DROP TABLE IF EXISTS Zones;
+DROP TABLE IF EXISTS Maps;
+DROP TABLE IF EXISTS SessionReports;
+DROP TABLE IF EXISTS Faults;
+DROP TABLE IF EXISTS FirmwareUpdates;
+DROP TABLE IF EXISTS FirmwareReleases;
+DROP TABLE IF EXISTS ModelPatterns;
+DROP TABLE IF EXISTS BatteryReadings;
+DROP TABLE IF EXISTS Events;
+DROP TABLE IF EXISTS ClockOffsets;
 DROP TABLE IF EXISTS Users;
 DROP TABLE IF EXISTS Commands;
 DROP TABLE IF EXISTS Robot;
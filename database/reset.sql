@@ -1,3 +1,16 @@
+TRUNCATE TABLE IF EXISTS WebhookDeliveries;
+TRUNCATE TABLE IF EXISTS Webhooks;
+TRUNCATE TABLE IF EXISTS Consumables;
+TRUNCATE TABLE IF EXISTS Zones;
+TRUNCATE TABLE IF EXISTS Maps;
+TRUNCATE TABLE IF EXISTS SessionReports;
+TRUNCATE TABLE IF EXISTS Faults;
+TRUNCATE TABLE IF EXISTS FirmwareUpdates;
+TRUNCATE TABLE IF EXISTS FirmwareReleases;
+TRUNCATE TABLE IF EXISTS ModelPatterns;
+TRUNCATE TABLE IF EXISTS BatteryReadings;
+TRUNCATE TABLE IF EXISTS Events;
+TRUNCATE TABLE IF EXISTS ClockOffsets;
 TRUNCATE TABLE IF EXISTS Users;
 TRUNCATE TABLE IF EXISTS Commands;
 TRUNCATE TABLE IF EXISTS Robot;
@@ -1,7 +1,19 @@
 CREATE TABLE IF NOT EXISTS Robot (
        robot_serial_number VARCHAR PRIMARY KEY,
        battery_level BIGINT NOT NULL DEFAULT 75,
-       assigned BOOLEAN NOT NULL DEFAULT FALSE
+       charging BOOLEAN NOT NULL DEFAULT FALSE,
+       assigned BOOLEAN NOT NULL DEFAULT FALSE,
+       state VARCHAR NOT NULL DEFAULT 'RobotState::Active',
+       model VARCHAR,
+       firmware_version VARCHAR,
+       capabilities TEXT[],
+       time_issued_tolerance BIGINT,
+       instruction_grace_period BIGINT,
+       battery_start_level BIGINT,
+       battery_abort_level BIGINT,
+       battery_resume_level BIGINT,
+       last_seen_at timestamptz,
+       last_init_at timestamptz
 );
 
 CREATE TABLE IF NOT EXISTS Users (
@@ -17,5 +29,136 @@ CREATE TABLE IF NOT EXISTS Commands (
        time_issued timestamptz NOT NULL,
        time_instruction timestamptz NOT NULL,
        instruction VARCHAR NOT NULL,
-       status VARCHAR NOT NULL DEFAULT 'Status::Pending'
+       parameters VARCHAR,
+       status VARCHAR NOT NULL DEFAULT 'Status::Pending',
+       lateness BIGINT,
+       interrupted BOOLEAN NOT NULL DEFAULT FALSE,
+       pause_reason VARCHAR,
+       paused_at timestamptz
+);
+
+CREATE TABLE IF NOT EXISTS ClockOffsets (
+       client_id VARCHAR PRIMARY KEY,
+       clock_offset BIGINT NOT NULL,
+       round_trip BIGINT,
+       updated_at timestamptz NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS Events (
+       event_id BIGSERIAL PRIMARY KEY,
+       robot_serial_number VARCHAR NOT NULL REFERENCES Robot(robot_serial_number),
+       time timestamptz NOT NULL,
+       event VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS BatteryReadings (
+       reading_id BIGSERIAL PRIMARY KEY,
+       robot_serial_number VARCHAR NOT NULL REFERENCES Robot(robot_serial_number),
+       time timestamptz NOT NULL,
+       battery_level BIGINT NOT NULL,
+       charging BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS battery_readings_robot_time ON BatteryReadings (robot_serial_number, time);
+
+CREATE TABLE IF NOT EXISTS ModelPatterns (
+       model VARCHAR NOT NULL,
+       pattern VARCHAR NOT NULL,
+       PRIMARY KEY (model, pattern)
+);
+
+CREATE TABLE IF NOT EXISTS FirmwareReleases (
+       release_id BIGSERIAL PRIMARY KEY,
+       version VARCHAR NOT NULL UNIQUE,
+       checksum VARCHAR NOT NULL,
+       target_models TEXT[] NOT NULL,
+       rollout_percentage BIGINT NOT NULL DEFAULT 0,
+       artifact_key VARCHAR NOT NULL,
+       created_at timestamptz NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS FirmwareUpdates (
+       robot_serial_number VARCHAR NOT NULL REFERENCES Robot(robot_serial_number),
+       release_id BIGINT NOT NULL REFERENCES FirmwareReleases(release_id),
+       status VARCHAR NOT NULL,
+       updated_at timestamptz NOT NULL,
+       PRIMARY KEY (robot_serial_number, release_id)
+);
+
+CREATE TABLE IF NOT EXISTS Faults (
+       fault_id BIGSERIAL PRIMARY KEY,
+       robot_serial_number VARCHAR NOT NULL REFERENCES Robot(robot_serial_number),
+       command_id BIGINT REFERENCES Commands(command_id),
+       code VARCHAR NOT NULL,
+       severity VARCHAR NOT NULL,
+       message VARCHAR NOT NULL,
+       time timestamptz NOT NULL,
+       acknowledged_at timestamptz,
+       cleared_at timestamptz
+);
+
+CREATE TABLE IF NOT EXISTS SessionReports (
+       command_id BIGINT PRIMARY KEY REFERENCES Commands(command_id),
+       robot_serial_number VARCHAR NOT NULL REFERENCES Robot(robot_serial_number),
+       duration BIGINT NOT NULL,
+       area_covered DOUBLE PRECISION NOT NULL,
+       battery_used BIGINT NOT NULL,
+       pauses BIGINT NOT NULL DEFAULT 0,
+       faults BIGINT NOT NULL DEFAULT 0,
+       completed_at timestamptz NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS Maps (
+       map_id BIGSERIAL PRIMARY KEY,
+       robot_serial_number VARCHAR NOT NULL REFERENCES Robot(robot_serial_number),
+       version BIGINT NOT NULL,
+       content_type VARCHAR NOT NULL,
+       resolution DOUBLE PRECISION NOT NULL,
+       origin_x DOUBLE PRECISION NOT NULL DEFAULT 0,
+       origin_y DOUBLE PRECISION NOT NULL DEFAULT 0,
+       size BIGINT NOT NULL,
+       storage_key VARCHAR NOT NULL,
+       created_at timestamptz NOT NULL,
+       UNIQUE (robot_serial_number, version)
+);
+
+CREATE TABLE IF NOT EXISTS Zones (
+       zone_id BIGSERIAL PRIMARY KEY,
+       robot_serial_number VARCHAR NOT NULL,
+       map_version BIGINT NOT NULL,
+       name VARCHAR NOT NULL,
+       kind VARCHAR NOT NULL,
+       polygon VARCHAR NOT NULL,
+       FOREIGN KEY (robot_serial_number, map_version) REFERENCES Maps(robot_serial_number, version)
+);
+
+CREATE TABLE IF NOT EXISTS Consumables (
+       robot_serial_number VARCHAR NOT NULL REFERENCES Robot(robot_serial_number),
+       component VARCHAR NOT NULL,
+       runtime BIGINT NOT NULL DEFAULT 0,
+       notified BOOLEAN NOT NULL DEFAULT FALSE,
+       reset_at timestamptz,
+       PRIMARY KEY (robot_serial_number, component)
+);
+
+CREATE TABLE IF NOT EXISTS Webhooks (
+       webhook_id BIGSERIAL PRIMARY KEY,
+       robot_serial_number VARCHAR NOT NULL REFERENCES Robot(robot_serial_number),
+       url VARCHAR NOT NULL,
+       event_types TEXT[] NOT NULL,
+       secret VARCHAR NOT NULL,
+       created_at timestamptz NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS WebhookDeliveries (
+       delivery_id BIGSERIAL PRIMARY KEY,
+       webhook_id BIGINT NOT NULL REFERENCES Webhooks(webhook_id) ON DELETE CASCADE,
+       event_id BIGINT NOT NULL REFERENCES Events(event_id),
+       status VARCHAR NOT NULL,
+       attempts BIGINT NOT NULL DEFAULT 0,
+       next_attempt_at timestamptz NOT NULL,
+       last_attempt_at timestamptz,
+       response_status BIGINT,
+       last_error VARCHAR,
+       created_at timestamptz NOT NULL
 );
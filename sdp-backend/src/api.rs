@@ -1,5 +1,16 @@
 pub mod admin;
 pub mod auth;
+pub mod battery;
 pub mod command;
+pub mod consumable;
+pub mod fault;
+pub mod firmware;
+pub mod map;
 pub mod poll;
+pub mod report;
+pub mod socket;
+pub mod stream;
+pub mod time;
 pub mod user;
+pub mod webhook;
+pub mod zone;
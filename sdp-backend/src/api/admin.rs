@@ -1,11 +1,131 @@
-use crate::robot::Robot;
+use crate::auth::Admin;
+use crate::firmware::FirmwareRelease;
+use crate::robot::{BatteryPolicyOverride, Robot, RobotModel};
+use crate::storage::LocalStorage;
 
-use actix_web::{post, web, web::Data, HttpResponse};
+use actix_web::{get, post, put, web, web::Bytes, web::Data, HttpResponse};
+use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TimeToleranceRequest {
+    pub robot_serial_number: String,
+    pub time_issued_tolerance: Option<i64>,
+    pub instruction_grace_period: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BatteryPolicyRequest {
+    pub robot_serial_number: String,
+    #[serde(flatten)]
+    pub policy: BatteryPolicyOverride,
+}
+
+// The details of an uploaded firmware artifact, target_models is a comma separated list
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FirmwareUploadRequest {
+    pub version: String,
+    pub checksum: String,
+    pub target_models: String,
+    #[serde(default)]
+    pub rollout_percentage: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FirmwareRolloutRequest {
+    pub release_id: i64,
+    pub rollout_percentage: i64,
+}
+
 #[post("/admin/robot")]
 pub async fn create_robot(conn: Data<PgPool>, user: web::Json<Robot>) -> HttpResponse {
-    Robot::new(&conn, &user.robot_serial_number)
+    Robot::new(&conn, &user.robot_serial_number, user.model.as_deref())
         .await
         .map_or_else(|e| e.into(), |user| HttpResponse::Ok().json(user))
 }
+
+#[put("/admin/robot/tolerance")]
+pub async fn set_time_tolerance(
+    _admin: Admin,
+    conn: Data<PgPool>,
+    tolerance: web::Json<TimeToleranceRequest>,
+) -> HttpResponse {
+    Robot::set_time_tolerance(
+        &conn,
+        &tolerance.robot_serial_number,
+        tolerance.time_issued_tolerance,
+        tolerance.instruction_grace_period,
+    )
+    .await
+    .map_or_else(|e| e.into(), |robot| HttpResponse::Ok().json(robot))
+}
+
+#[put("/admin/robot/battery-policy")]
+pub async fn set_battery_policy(
+    _admin: Admin,
+    conn: Data<PgPool>,
+    policy: web::Json<BatteryPolicyRequest>,
+) -> HttpResponse {
+    Robot::set_battery_policy(&conn, &policy.robot_serial_number, &policy.policy)
+        .await
+        .map_or_else(|e| e.into(), |robot| HttpResponse::Ok().json(robot))
+}
+
+#[put("/admin/model")]
+pub async fn set_model_patterns(
+    _admin: Admin,
+    conn: Data<PgPool>,
+    model: web::Json<RobotModel>,
+) -> HttpResponse {
+    RobotModel::set_patterns(&conn, &model.model, &model.patterns)
+        .await
+        .map_or_else(|e| e.into(), |model| HttpResponse::Ok().json(model))
+}
+
+// Upload a firmware artifact as the request body, with the release details in the query.
+// This is registered in main so the larger upload limit only applies to this route.
+pub async fn upload_firmware(
+    _admin: Admin,
+    conn: Data<PgPool>,
+    storage: Data<LocalStorage>,
+    release: web::Query<FirmwareUploadRequest>,
+    artifact: Bytes,
+) -> HttpResponse {
+    let target_models: Vec<String> = release
+        .target_models
+        .split(',')
+        .map(|m| m.trim())
+        .filter(|m| !m.is_empty())
+        .map(|m| m.to_string())
+        .collect();
+
+    FirmwareRelease::new(
+        &conn,
+        storage.get_ref(),
+        &release.version,
+        &release.checksum,
+        &target_models,
+        release.rollout_percentage,
+        &artifact,
+    )
+    .await
+    .map_or_else(|e| e.into(), |release| HttpResponse::Ok().json(release))
+}
+
+#[get("/admin/firmware")]
+pub async fn get_firmware_releases(_admin: Admin, conn: Data<PgPool>) -> HttpResponse {
+    FirmwareRelease::get_all(&conn)
+        .await
+        .map_or_else(|e| e.into(), |releases| HttpResponse::Ok().json(releases))
+}
+
+#[put("/admin/firmware/rollout")]
+pub async fn set_firmware_rollout(
+    _admin: Admin,
+    conn: Data<PgPool>,
+    rollout: web::Json<FirmwareRolloutRequest>,
+) -> HttpResponse {
+    FirmwareRelease::set_rollout(&conn, rollout.release_id, rollout.rollout_percentage)
+        .await
+        .map_or_else(|e| e.into(), |release| HttpResponse::Ok().json(release))
+}
@@ -0,0 +1,70 @@
+use crate::battery::BatteryReading;
+use crate::error::ApiError;
+use crate::robot::{BatteryPolicyOverride, Robot};
+use crate::user::User;
+
+use actix_web::{get, put, web, web::Data, HttpResponse};
+use chrono::{Duration, TimeZone, Utc};
+use serde::Deserialize;
+use sqlx::postgres::PgPool;
+
+// By default give the last day of readings at five minute resolution
+const DEFAULT_HISTORY_HOURS: i64 = 24;
+const DEFAULT_RESOLUTION: i64 = 300;
+
+// The times are in seconds since the epoch, the resolution is in seconds
+#[derive(Deserialize, Debug)]
+pub struct BatteryQuery {
+    from: Option<i64>,
+    to: Option<i64>,
+    resolution: Option<i64>,
+}
+
+#[get("/battery")]
+pub async fn battery_history(
+    conn: Data<PgPool>,
+    user: User,
+    query: web::Query<BatteryQuery>,
+) -> HttpResponse {
+    let to = match query.to {
+        Some(t) => Utc.timestamp_opt(t, 0).single(),
+        None => Some(chrono::Utc::now()),
+    };
+    let from = match query.from {
+        Some(t) => Utc.timestamp_opt(t, 0).single(),
+        None => to.and_then(|t| t.checked_sub_signed(Duration::hours(DEFAULT_HISTORY_HOURS))),
+    };
+    let (from, to) = match (from, to) {
+        (Some(from), Some(to)) => (from, to),
+        _out_of_range => return ApiError::InvalidTimeRange.into(),
+    };
+    let resolution = query.resolution.unwrap_or(DEFAULT_RESOLUTION);
+
+    BatteryReading::history(&conn, &user.robot_serial_number, from, to, resolution)
+        .await
+        .map_or_else(|e| e.into(), |b| HttpResponse::Ok().json(b))
+}
+
+#[get("/battery/policy")]
+pub async fn get_battery_policy(conn: Data<PgPool>, user: User) -> HttpResponse {
+    Robot::get_by_serial(&conn, &user.robot_serial_number)
+        .await
+        .map_or_else(
+            |e| e.into(),
+            |r| HttpResponse::Ok().json(r.battery_policy()),
+        )
+}
+
+#[put("/battery/policy")]
+pub async fn set_battery_policy(
+    conn: Data<PgPool>,
+    user: User,
+    policy: web::Json<BatteryPolicyOverride>,
+) -> HttpResponse {
+    Robot::set_battery_policy(&conn, &user.robot_serial_number, &policy)
+        .await
+        .map_or_else(
+            |e| e.into(),
+            |r| HttpResponse::Ok().json(r.battery_policy()),
+        )
+}
@@ -1,10 +1,12 @@
-use crate::command::{Command, Instruction};
+use crate::clock::{Client, ClockOffset};
+use crate::command::{Command, Instruction, TaskParameters};
 use crate::error::ApiError;
+use crate::report::SessionReport;
 use crate::user::User;
 
 use actix_web::{delete, get, post, web, web::Data, HttpRequest, HttpResponse};
 use chrono::{serde::ts_seconds, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
 use std::str::FromStr;
 
@@ -15,6 +17,17 @@ pub struct CommandRequest {
     // time_instruction: Vec<chrono::DateTime<Utc>>,
     time_instruction: Vec<u64>,
     instruction: Instruction,
+    // How to carry out a task, parameters that are not given use the defaults for the pattern
+    #[serde(default)]
+    parameters: Option<TaskParameters>,
+}
+
+// A command with the report the robot sent when it completed the command
+#[derive(Serialize, Debug)]
+pub struct CommandResponse {
+    #[serde(flatten)]
+    command: Command,
+    report: Option<SessionReport>,
 }
 
 #[post("/command")]
@@ -29,13 +42,32 @@ pub async fn create_command(
             Err(e) => return e.into(),
         };
 
+    // Correct the times for the skew of the users clock, so they are validated against server time
+    let clock_offset = match ClockOffset::get(&conn, &Client::User(user.user_name.clone())).await {
+        Ok(o) => o,
+        Err(e) => return e.into(),
+    };
+    let time_instruction = match time_instruction
+        .into_iter()
+        .map(|t| clock_offset.server_time(t))
+        .collect()
+    {
+        Some(t) => t,
+        None => return ApiError::InvalidTimeInstruction.into(),
+    };
+    let time_issued = match clock_offset.server_time(cmd.time_issued) {
+        Some(t) => t,
+        None => return ApiError::InvalidTimeIssued.into(),
+    };
+
     Command::batch_new(
         &conn,
         &user.robot_serial_number,
-        cmd.time_issued,
+        time_issued,
         time_instruction,
         // cmd.time_instruction.clone(),
         &cmd.instruction,
+        &cmd.parameters,
     )
     .await
     .map_or_else(|e| e.into(), |cmd| HttpResponse::Ok().json(cmd))
@@ -54,7 +86,12 @@ pub async fn get_command(conn: Data<PgPool>, user: User, req: HttpRequest) -> Ht
                 Err(e) => e.into(),
                 // If the logged in users rsn is the same as the one for the command, return it
                 Ok(c) if user.robot_serial_number == c.robot_serial_number => {
-                    HttpResponse::Ok().json(c)
+                    match SessionReport::get_by_command(&conn, c.command_id).await {
+                        Ok(report) => {
+                            HttpResponse::Ok().json(CommandResponse { command: c, report })
+                        }
+                        Err(e) => e.into(),
+                    }
                 }
                 // If the user is not allowed to view the command, return an error
                 Ok(_) => ApiError::AuthenticationFailed.into(),
@@ -102,7 +139,7 @@ struct Time {
 fn convert_time(times: Vec<u64>) -> Result<Vec<chrono::DateTime<Utc>>, ApiError> {
     let mut time_instructions = Vec::new();
     for t in times {
-        let json_time = format!("{{\"time\": {}}}", t.to_string());
+        let json_time = format!("{{\"time\": {}}}", t);
         let formatted_time: Time =
             serde_json::from_str(&json_time).map_err(|_| ApiError::SerializationError)?;
         time_instructions.push(formatted_time.time);
@@ -0,0 +1,25 @@
+use crate::consumable::{Component, Consumable};
+use crate::user::User;
+
+use actix_web::{get, post, web, web::Data, HttpResponse};
+use sqlx::postgres::PgPool;
+
+// How worn the components of the users robot are
+#[get("/consumable")]
+pub async fn get_consumables(conn: Data<PgPool>, user: User) -> HttpResponse {
+    Consumable::get_by_robot(&conn, &user.robot_serial_number)
+        .await
+        .map_or_else(|e| e.into(), |cs| HttpResponse::Ok().json(cs))
+}
+
+// Called after the user has replaced the component
+#[post("/consumable/{component}/reset")]
+pub async fn reset_consumable(
+    conn: Data<PgPool>,
+    user: User,
+    component: web::Path<Component>,
+) -> HttpResponse {
+    Consumable::reset(&conn, &user.robot_serial_number, *component)
+        .await
+        .map_or_else(|e| e.into(), |c| HttpResponse::Ok().json(c))
+}
@@ -0,0 +1,31 @@
+use crate::error::ApiError;
+use crate::fault::Fault;
+use crate::user::User;
+
+use actix_web::{get, post, web, web::Data, HttpResponse};
+use sqlx::postgres::PgPool;
+
+// The faults for the users robot that have not been acknowledged
+#[get("/fault")]
+pub async fn active_faults(conn: Data<PgPool>, user: User) -> HttpResponse {
+    Fault::get_active(&conn, &user.robot_serial_number)
+        .await
+        .map_or_else(|e| e.into(), |fs| HttpResponse::Ok().json(fs))
+}
+
+#[post("/fault/{fault_id}/acknowledge")]
+pub async fn acknowledge_fault(
+    conn: Data<PgPool>,
+    user: User,
+    fault_id: web::Path<i64>,
+) -> HttpResponse {
+    match Fault::get_by_id(&conn, *fault_id).await {
+        Err(e) => e.into(),
+        // Users can only acknowledge the faults of their own robot
+        Ok(f) if f.robot_serial_number == user.robot_serial_number => f
+            .acknowledge(&conn)
+            .await
+            .map_or_else(|e| e.into(), |f| HttpResponse::Ok().json(f)),
+        Ok(_) => ApiError::AuthenticationFailed.into(),
+    }
+}
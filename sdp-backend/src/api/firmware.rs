@@ -0,0 +1,36 @@
+use crate::firmware::{FirmwareRelease, FirmwareUpdate};
+use crate::storage::LocalStorage;
+use crate::user::User;
+
+use actix_web::{get, web, web::Data, HttpResponse};
+use sqlx::postgres::PgPool;
+
+// Download the artifact for a release, used by robots that have been offered an update
+#[get("/firmware/{release_id}")]
+pub async fn download_firmware(
+    conn: Data<PgPool>,
+    storage: Data<LocalStorage>,
+    release_id: web::Path<i64>,
+) -> HttpResponse {
+    let release = match FirmwareRelease::get(&conn, *release_id).await {
+        Ok(r) => r,
+        Err(e) => return e.into(),
+    };
+
+    release.artifact(storage.get_ref()).await.map_or_else(
+        |e| e.into(),
+        |a| {
+            HttpResponse::Ok()
+                .content_type("application/octet-stream")
+                .body(a)
+        },
+    )
+}
+
+// The firmware updates for the users robot
+#[get("/firmware")]
+pub async fn firmware_updates(conn: Data<PgPool>, user: User) -> HttpResponse {
+    FirmwareUpdate::get_by_robot(&conn, &user.robot_serial_number)
+        .await
+        .map_or_else(|e| e.into(), |us| HttpResponse::Ok().json(us))
+}
@@ -0,0 +1,81 @@
+use crate::map::{Map, MapMetadata};
+use crate::storage::LocalStorage;
+use crate::user::User;
+
+use actix_web::{get, http::header, web, web::Bytes, web::Data, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+// The metadata fields are listed here rather than flattened,
+// as flattened numbers can not be read from a query string
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MapUploadRequest {
+    pub robot_serial_number: String,
+    pub resolution: f64,
+    #[serde(default)]
+    pub origin_x: f64,
+    #[serde(default)]
+    pub origin_y: f64,
+}
+
+// The robot uploads the map data as the request body, with the metadata in the query.
+// This is registered in main so the larger upload limit only applies to this route.
+pub async fn upload_map(
+    conn: Data<PgPool>,
+    storage: Data<LocalStorage>,
+    req: HttpRequest,
+    map: web::Query<MapUploadRequest>,
+    data: Bytes,
+) -> HttpResponse {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|c| c.to_str().ok())
+        .unwrap_or("application/octet-stream");
+
+    Map::new(
+        &conn,
+        storage.get_ref(),
+        &map.robot_serial_number,
+        content_type,
+        &MapMetadata {
+            resolution: map.resolution,
+            origin_x: map.origin_x,
+            origin_y: map.origin_y,
+        },
+        &data,
+    )
+    .await
+    .map_or_else(|e| e.into(), |m| HttpResponse::Ok().json(m))
+}
+
+// The versions of the map for the users robot
+#[get("/map")]
+pub async fn get_maps(conn: Data<PgPool>, user: User) -> HttpResponse {
+    Map::get_by_robot(&conn, &user.robot_serial_number)
+        .await
+        .map_or_else(|e| e.into(), |ms| HttpResponse::Ok().json(ms))
+}
+
+// Download a version of the map for the users robot
+#[get("/map/{version}")]
+pub async fn download_map(
+    conn: Data<PgPool>,
+    storage: Data<LocalStorage>,
+    user: User,
+    version: web::Path<i64>,
+) -> HttpResponse {
+    let map = match Map::get_by_version(&conn, &user.robot_serial_number, *version).await {
+        Ok(m) => m,
+        Err(e) => return e.into(),
+    };
+
+    map.data(storage.get_ref()).await.map_or_else(
+        |e| e.into(),
+        |d| {
+            HttpResponse::Ok()
+                .content_type(map.content_type.clone())
+                .body(d)
+        },
+    )
+}
@@ -1,19 +1,166 @@
+use crate::error::ApiError;
 use crate::poll::Init;
 use crate::poll::Poll;
+use crate::poll::PollResponse;
+use crate::signing::CommandSigner;
+use crate::stream::EventHub;
+use crate::wire::{WireBody, WireFormat};
 
-use actix_web::{get, web, web::Data, HttpResponse};
+use actix::Addr;
+use actix_web::http::header::{ETag, EntityTag, IfNoneMatch, VARY};
+use actix_web::http::{HeaderName, HeaderValue};
+use actix_web::{get, post, web, web::Data, HttpMessage, HttpRequest, HttpResponse};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use sqlx::postgres::PgPool;
 
+// Sent instead of the poll response when the robot already has it
+#[derive(Serialize, Debug)]
+struct Unchanged {
+    unchanged: bool,
+}
+
+#[post("/robot/{serial}/poll")]
+pub async fn robot_poll(
+    req: HttpRequest,
+    conn: Data<PgPool>,
+    signer: Data<CommandSigner>,
+    hub: Data<Addr<EventHub>>,
+    serial: web::Path<String>,
+    poll: Result<WireBody<Poll>, ApiError>,
+) -> HttpResponse {
+    let format = WireFormat::accepted(&req);
+    let poll = match poll {
+        Ok(p) => p,
+        Err(e) => return format.error_response(e),
+    };
+
+    // The robot can only poll for itself
+    if *serial != poll.robot_serial_number {
+        return format.error_response(ApiError::AuthenticationFailed);
+    }
+
+    let res = match Poll::long_poll(&conn, &hub, &poll).await {
+        Ok(cmd) => PollResponse::new(&conn, &signer, cmd).await,
+        Err(e) => Err(e),
+    };
+    conditional_response(&req, res)
+}
+
+#[post("/robot/{serial}/init")]
+pub async fn robot_init(
+    req: HttpRequest,
+    conn: Data<PgPool>,
+    signer: Data<CommandSigner>,
+    serial: web::Path<String>,
+    init: Result<WireBody<Init>, ApiError>,
+) -> HttpResponse {
+    let format = WireFormat::accepted(&req);
+    let init = match init {
+        Ok(i) => i,
+        Err(e) => return format.error_response(e),
+    };
+
+    if *serial != init.robot_serial_number {
+        return format.error_response(ApiError::AuthenticationFailed);
+    }
+
+    let res = match Init::init(&conn, &init).await {
+        Ok(cmd) => PollResponse::new(&conn, &signer, cmd).await,
+        Err(e) => Err(e),
+    };
+    conditional_response(&req, res)
+}
+
+// The public key robots check the signed commands with
+#[get("/robot/signing-key")]
+pub async fn signing_key(signer: Data<CommandSigner>) -> HttpResponse {
+    HttpResponse::Ok().json(signer.public_key())
+}
+
+// The routes the robots used before the POST routes, these are served unless
+// LEGACY_ROBOT_ROUTES is set to false
 #[get("/poll")]
-pub async fn robot_poll(conn: Data<PgPool>, poll: web::Json<Poll>) -> HttpResponse {
-    Poll::poll(&conn, &poll)
-        .await
-        .map_or_else(|e| e.into(), |cmd| HttpResponse::Ok().json(cmd))
+pub async fn legacy_robot_poll(
+    req: HttpRequest,
+    conn: Data<PgPool>,
+    signer: Data<CommandSigner>,
+    hub: Data<Addr<EventHub>>,
+    poll: Result<WireBody<Poll>, ApiError>,
+) -> HttpResponse {
+    let poll = match poll {
+        Ok(p) => p,
+        Err(e) => return deprecated(WireFormat::accepted(&req).error_response(e)),
+    };
+
+    let res = match Poll::long_poll(&conn, &hub, &poll).await {
+        Ok(cmd) => PollResponse::new(&conn, &signer, cmd).await,
+        Err(e) => Err(e),
+    };
+    deprecated(conditional_response(&req, res))
 }
 
 #[get("/init")]
-pub async fn robot_init(conn: Data<PgPool>, init: web::Json<Init>) -> HttpResponse {
-    Init::init(&conn, &init)
-        .await
-        .map_or_else(|e| e.into(), |cmd| HttpResponse::Ok().json(cmd))
+pub async fn legacy_robot_init(
+    req: HttpRequest,
+    conn: Data<PgPool>,
+    signer: Data<CommandSigner>,
+    init: Result<WireBody<Init>, ApiError>,
+) -> HttpResponse {
+    let init = match init {
+        Ok(i) => i,
+        Err(e) => return deprecated(WireFormat::accepted(&req).error_response(e)),
+    };
+
+    let res = match Init::init(&conn, &init).await {
+        Ok(cmd) => PollResponse::new(&conn, &signer, cmd).await,
+        Err(e) => Err(e),
+    };
+    deprecated(conditional_response(&req, res))
+}
+
+// Send the response in the format the robot accepts, with an ETag of its contents.
+// If the robot sends the ETag of the response it already has in If-None-Match and
+// nothing has changed, {"unchanged": true} is sent in place of the response.
+fn conditional_response(req: &HttpRequest, res: Result<PollResponse, ApiError>) -> HttpResponse {
+    let format = WireFormat::accepted(req);
+    let res = match res {
+        Ok(r) => r,
+        Err(e) => return format.error_response(e),
+    };
+    let body = match format.encode(&res) {
+        Ok(b) => b,
+        Err(e) => return format.error_response(e),
+    };
+
+    let etag = EntityTag::strong(format!("{:x}", Sha256::digest(&body)));
+    let unchanged = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|t| t.weak_eq(&etag)),
+        None => false,
+    };
+
+    let body = if unchanged {
+        match format.encode(&Unchanged { unchanged: true }) {
+            Ok(b) => b,
+            Err(e) => return format.error_response(e),
+        }
+    } else {
+        body
+    };
+
+    HttpResponse::Ok()
+        .set(ETag(etag))
+        .header(VARY, "Accept")
+        .content_type(format.mime())
+        .body(body)
+}
+
+// Mark the response from a legacy route as deprecated
+fn deprecated(mut res: HttpResponse) -> HttpResponse {
+    res.headers_mut().insert(
+        HeaderName::from_static("deprecation"),
+        HeaderValue::from_static("true"),
+    );
+    res
 }
@@ -0,0 +1,24 @@
+use crate::report::{SessionReport, StatsPeriod};
+use crate::user::User;
+
+use actix_web::{get, web, web::Data, HttpResponse};
+use serde::Deserialize;
+use sqlx::postgres::PgPool;
+
+#[derive(Deserialize, Debug)]
+pub struct StatsQuery {
+    #[serde(default)]
+    period: StatsPeriod,
+}
+
+// The total area cleaned and runtime of the users robot per week or month
+#[get("/stats")]
+pub async fn session_stats(
+    conn: Data<PgPool>,
+    user: User,
+    query: web::Query<StatsQuery>,
+) -> HttpResponse {
+    SessionReport::stats(&conn, &user.robot_serial_number, query.period)
+        .await
+        .map_or_else(|e| e.into(), |s| HttpResponse::Ok().json(s))
+}
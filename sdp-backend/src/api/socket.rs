@@ -0,0 +1,137 @@
+// Push updates for the user's robot over a WebSocket at /ws.
+//
+// The user's token is sent in the Authorization header as for the other endpoints.
+// Each new event for the robot is sent as a text message holding the event as JSON:
+//
+//   {"event_id": 12, "robot_serial_number": "serial1", "time": 1610000000, "event": ...}
+//
+// where time is in seconds and event is one of:
+//
+//   "Online" / "Offline"                     the robot started or stopped polling
+//   {"CommandStatus": {"command_id": 3, "status": "InProgress", "instruction": "ReturnToDock"}}
+//                                            the status of a command changed, instruction
+//                                            is what the command was for, or null
+//   {"Battery": {"battery_level": 80, "charging": false}}
+//                                            the robot reported a new battery level
+//   {"Fault": 4}                             a new fault, see /fault
+//   {"ObstaclePaused": 3} / {"ObstacleCleared": 3} / {"ObstacleTimeout": 3}
+//                                            the command was paused, resumed or cancelled
+//                                            because of an obstacle
+//   {"MaintenanceDue": "Filter"}             a component should be replaced
+//   {"StateChanged": "Charging"}             the robot is now "Active", "Docked" or "Charging"
+//
+// Messages sent by the app are ignored. The server pings every few seconds and closes
+// the socket when the app stops answering.
+
+use crate::stream::{EventHub, EventMessage, Subscribe, Unsubscribe};
+use crate::user::User;
+
+use actix::prelude::*;
+use actix_web::{get, web, web::Data, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use std::time::{Duration, Instant};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct EventSocket {
+    robot_serial_number: String,
+    hub: Addr<EventHub>,
+    subscription: Option<usize>,
+    // The last time the app answered
+    heartbeat: Instant,
+}
+
+impl EventSocket {
+    fn new(robot_serial_number: String, hub: Addr<EventHub>) -> Self {
+        Self {
+            robot_serial_number,
+            hub,
+            subscription: None,
+            heartbeat: Instant::now(),
+        }
+    }
+
+    // Ping the app, closing the socket if it has not answered in time
+    fn start_heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |socket, ctx| {
+            if Instant::now().duration_since(socket.heartbeat) > CLIENT_TIMEOUT {
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+}
+
+impl Actor for EventSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.start_heartbeat(ctx);
+
+        self.hub
+            .send(Subscribe {
+                robot_serial_number: self.robot_serial_number.clone(),
+                recipient: ctx.address().recipient(),
+            })
+            .into_actor(self)
+            .then(|res, socket, ctx| {
+                match res {
+                    Ok(id) => socket.subscription = Some(id),
+                    Err(_) => ctx.stop(),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        if let Some(id) = self.subscription {
+            self.hub.do_send(Unsubscribe(id));
+        }
+        Running::Stop
+    }
+}
+
+impl Handler<EventMessage> for EventSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: EventMessage, ctx: &mut Self::Context) {
+        if let Ok(event_json) = serde_json::to_string(&msg.0) {
+            ctx.text(event_json);
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for EventSocket {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(m)) => {
+                self.heartbeat = Instant::now();
+                ctx.pong(&m);
+            }
+            Ok(ws::Message::Pong(_)) => self.heartbeat = Instant::now(),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(_) => ctx.stop(),
+            _ => (),
+        }
+    }
+}
+
+#[get("/ws")]
+pub async fn event_socket(
+    req: HttpRequest,
+    stream: web::Payload,
+    user: User,
+    hub: Data<Addr<EventHub>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    ws::start(
+        EventSocket::new(user.robot_serial_number, hub.get_ref().clone()),
+        &req,
+        stream,
+    )
+}
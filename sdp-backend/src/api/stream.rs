@@ -0,0 +1,143 @@
+// Server-Sent Events stream at /events/stream, for clients that can not use the WebSocket.
+//
+// The same events as the WebSocket at /ws are sent, each as one SSE message with the
+// event id and the event JSON as the data:
+//
+//   id: 12
+//   data: {"event_id": 12, "robot_serial_number": "serial1", "time": 1610000000, "event": ...}
+//
+// A client that reconnects with the Last-Event-ID header is first sent the events it
+// missed, as long as they are inside the replay window. A comment line is sent every
+// few seconds to keep the connection open.
+
+use crate::event::Event;
+use crate::stream::{EventHub, EventMessage, Subscribe, Unsubscribe};
+use crate::user::User;
+
+use actix::prelude::*;
+use actix_web::{get, web::Bytes, web::Data, HttpRequest, HttpResponse};
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::StreamExt;
+use sqlx::postgres::PgPool;
+use std::time::Duration;
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+pub struct EventStream {
+    robot_serial_number: String,
+    hub: Addr<EventHub>,
+    conn: PgPool,
+    subscription: Option<usize>,
+    // The last event sent to the client
+    last_event_id: Option<i64>,
+    body: UnboundedSender<Bytes>,
+}
+
+impl EventStream {
+    // Write to the response, stopping when the client has gone away
+    fn send(&mut self, data: String, ctx: &mut Context<Self>) {
+        if self.body.unbounded_send(Bytes::from(data)).is_err() {
+            ctx.stop();
+        }
+    }
+
+    fn send_event(&mut self, event: &Event, ctx: &mut Context<Self>) {
+        // Events already sent in the replay are skipped
+        if matches!(self.last_event_id, Some(id) if event.event_id <= id) {
+            return;
+        }
+        self.last_event_id = Some(event.event_id);
+
+        let event_json = serde_json::to_string(event).unwrap_or_default();
+        self.send(
+            format!("id: {}\ndata: {}\n\n", event.event_id, event_json),
+            ctx,
+        );
+    }
+}
+
+impl Actor for EventStream {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(KEEP_ALIVE_INTERVAL, |stream, ctx| {
+            stream.send(":\n\n".to_string(), ctx);
+        });
+
+        // Subscribe before the replay is read so no events are missed in between,
+        // new events wait in the mailbox until the replay has been sent
+        self.hub
+            .send(Subscribe {
+                robot_serial_number: self.robot_serial_number.clone(),
+                recipient: ctx.address().recipient(),
+            })
+            .into_actor(self)
+            .then(|res, stream, ctx| {
+                match res {
+                    Ok(id) => stream.subscription = Some(id),
+                    Err(_) => ctx.stop(),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+
+        if let Some(last_event_id) = self.last_event_id {
+            let conn = self.conn.clone();
+            let robot_serial_number = self.robot_serial_number.clone();
+            async move { Event::get_since(&conn, &robot_serial_number, last_event_id).await }
+                .into_actor(self)
+                .then(|res, stream, ctx| {
+                    for event in res.unwrap_or_default() {
+                        stream.send_event(&event, ctx);
+                    }
+                    fut::ready(())
+                })
+                .wait(ctx);
+        }
+    }
+
+    fn stopping(&mut self, _: &mut Context<Self>) -> Running {
+        if let Some(id) = self.subscription {
+            self.hub.do_send(Unsubscribe(id));
+        }
+        Running::Stop
+    }
+}
+
+impl Handler<EventMessage> for EventStream {
+    type Result = ();
+
+    fn handle(&mut self, msg: EventMessage, ctx: &mut Context<Self>) {
+        self.send_event(&msg.0, ctx);
+    }
+}
+
+#[get("/events/stream")]
+pub async fn event_stream(
+    req: HttpRequest,
+    conn: Data<PgPool>,
+    user: User,
+    hub: Data<Addr<EventHub>>,
+) -> HttpResponse {
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse().ok());
+
+    let (body, response) = unbounded();
+    EventStream {
+        robot_serial_number: user.robot_serial_number,
+        hub: hub.get_ref().clone(),
+        conn: conn.get_ref().clone(),
+        subscription: None,
+        last_event_id,
+        body,
+    }
+    .start();
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(response.map(Ok::<_, actix_web::Error>))
+}
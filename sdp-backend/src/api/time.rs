@@ -0,0 +1,22 @@
+use crate::clock::{Client, TimeRequest};
+use crate::user::User;
+
+use actix_web::{post, web, web::Data, HttpResponse};
+use sqlx::postgres::PgPool;
+
+// Logged in users have their offset tracked by user name, anyone else is
+// only sent the times
+#[post("/time")]
+pub async fn time_sync(
+    conn: Data<PgPool>,
+    user: Option<User>,
+    req: web::Json<TimeRequest>,
+) -> HttpResponse {
+    let server_receive = chrono::Utc::now();
+
+    let client = user.map(|u| Client::User(u.user_name));
+
+    req.sync(&conn, client, server_receive)
+        .await
+        .map_or_else(|e| e.into(), |t| HttpResponse::Ok().json(t))
+}
@@ -2,6 +2,7 @@ use actix_web::{get, post, web, web::Data, HttpResponse};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
 
+use crate::event::Event;
 use crate::robot::Robot;
 use crate::user::User;
 
@@ -26,7 +27,43 @@ pub async fn create_user(conn: Data<PgPool>, user: web::Json<UserRequest>) -> Ht
 
 #[get("/user")]
 pub async fn user_status(conn: Data<PgPool>, user: User) -> HttpResponse {
-    Robot::get_by_serial(&conn, &user.robot_serial_number)
+    let robot = match Robot::get_by_serial(&conn, &user.robot_serial_number).await {
+        Ok(r) => r,
+        Err(e) => return e.into(),
+    };
+
+    robot
+        .refresh_presence(&conn)
         .await
         .map_or_else(|e| e.into(), |r| HttpResponse::Ok().json(r))
 }
+
+// The cleaning patterns the users robot is able to run
+#[get("/patterns")]
+pub async fn user_patterns(conn: Data<PgPool>, user: User) -> HttpResponse {
+    let robot = match Robot::get_by_serial(&conn, &user.robot_serial_number).await {
+        Ok(r) => r,
+        Err(e) => return e.into(),
+    };
+
+    robot
+        .supported_patterns(&conn)
+        .await
+        .map_or_else(|e| e.into(), |ps| HttpResponse::Ok().json(ps))
+}
+
+#[get("/events")]
+pub async fn user_events(conn: Data<PgPool>, user: User) -> HttpResponse {
+    // Make sure the robot going offline has been recorded
+    let robot = match Robot::get_by_serial(&conn, &user.robot_serial_number).await {
+        Ok(r) => r,
+        Err(e) => return e.into(),
+    };
+    if let Err(e) = robot.refresh_presence(&conn).await {
+        return e.into();
+    }
+
+    Event::get_by_robot(&conn, &user.robot_serial_number)
+        .await
+        .map_or_else(|e| e.into(), |es| HttpResponse::Ok().json(es))
+}
@@ -0,0 +1,73 @@
+use crate::error::ApiError;
+use crate::user::User;
+use crate::webhook::{Webhook, WebhookDelivery};
+
+use actix_web::{delete, get, post, web, web::Data, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookRequest {
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub secret: String,
+}
+
+#[get("/webhook")]
+pub async fn get_webhooks(conn: Data<PgPool>, user: User) -> HttpResponse {
+    Webhook::get_by_robot(&conn, &user.robot_serial_number)
+        .await
+        .map_or_else(|e| e.into(), |ws| HttpResponse::Ok().json(ws))
+}
+
+#[post("/webhook")]
+pub async fn create_webhook(
+    conn: Data<PgPool>,
+    user: User,
+    webhook: web::Json<WebhookRequest>,
+) -> HttpResponse {
+    Webhook::new(
+        &conn,
+        &user.robot_serial_number,
+        &webhook.url,
+        &webhook.event_types,
+        &webhook.secret,
+    )
+    .await
+    .map_or_else(|e| e.into(), |w| HttpResponse::Ok().json(w))
+}
+
+#[delete("/webhook/{webhook_id}")]
+pub async fn delete_webhook(
+    conn: Data<PgPool>,
+    user: User,
+    webhook_id: web::Path<i64>,
+) -> HttpResponse {
+    match Webhook::get_by_id(&conn, *webhook_id).await {
+        Err(e) => e.into(),
+        // Users can only remove the webhooks for their own robot
+        Ok(w) if w.robot_serial_number == user.robot_serial_number => w
+            .delete(&conn)
+            .await
+            .map_or_else(|e| e.into(), |_| HttpResponse::Ok().json(w)),
+        Ok(_) => ApiError::AuthenticationFailed.into(),
+    }
+}
+
+// The recent deliveries to the webhook, newest first
+#[get("/webhook/{webhook_id}/deliveries")]
+pub async fn webhook_deliveries(
+    conn: Data<PgPool>,
+    user: User,
+    webhook_id: web::Path<i64>,
+) -> HttpResponse {
+    match Webhook::get_by_id(&conn, *webhook_id).await {
+        Err(e) => e.into(),
+        Ok(w) if w.robot_serial_number == user.robot_serial_number => {
+            WebhookDelivery::get_by_webhook(&conn, w.webhook_id)
+                .await
+                .map_or_else(|e| e.into(), |ds| HttpResponse::Ok().json(ds))
+        }
+        Ok(_) => ApiError::AuthenticationFailed.into(),
+    }
+}
@@ -0,0 +1,55 @@
+use crate::error::ApiError;
+use crate::user::User;
+use crate::zone::{Point, Zone, ZoneKind};
+
+use actix_web::{delete, get, post, web, web::Data, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ZoneRequest {
+    pub map_version: i64,
+    #[serde(default)]
+    pub name: String,
+    pub kind: ZoneKind,
+    pub polygon: Vec<Point>,
+}
+
+// The zones on the current map of the users robot
+#[get("/zone")]
+pub async fn get_zones(conn: Data<PgPool>, user: User) -> HttpResponse {
+    Zone::get_current(&conn, &user.robot_serial_number)
+        .await
+        .map_or_else(|e| e.into(), |zs| HttpResponse::Ok().json(zs))
+}
+
+#[post("/zone")]
+pub async fn create_zone(
+    conn: Data<PgPool>,
+    user: User,
+    zone: web::Json<ZoneRequest>,
+) -> HttpResponse {
+    Zone::new(
+        &conn,
+        &user.robot_serial_number,
+        zone.map_version,
+        &zone.name,
+        &zone.kind,
+        &zone.polygon,
+    )
+    .await
+    .map_or_else(|e| e.into(), |z| HttpResponse::Ok().json(z))
+}
+
+#[delete("/zone/{zone_id}")]
+pub async fn delete_zone(conn: Data<PgPool>, user: User, zone_id: web::Path<i64>) -> HttpResponse {
+    match Zone::get_by_id(&conn, *zone_id).await {
+        Err(e) => e.into(),
+        // Users can only remove the zones of their own robot
+        Ok(z) if z.robot_serial_number == user.robot_serial_number => z
+            .delete(&conn)
+            .await
+            .map_or_else(|e| e.into(), |_| HttpResponse::Ok().json(z)),
+        Ok(_) => ApiError::AuthenticationFailed.into(),
+    }
+}
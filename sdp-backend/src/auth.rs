@@ -1,7 +1,9 @@
+use crate::config;
 use crate::error::ApiError;
 use crate::user::User;
 use actix_web::{dev, http::header, web::Data, FromRequest, HttpRequest};
 use chrono::{Duration, Utc};
+use futures::future::{ready, Ready};
 use futures::Future;
 // use futures_util::future::{err, ok, Ready};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
@@ -18,6 +20,11 @@ pub struct Token {
     pub token: String,
 }
 
+// An operator allowed to use the admin routes, given by the ADMIN_TOKEN
+// in the AUTH header
+#[derive(Debug)]
+pub struct Admin;
+
 // TODO: Only store the username
 #[derive(Serialize, Deserialize, Debug)]
 struct Claims {
@@ -85,3 +92,33 @@ impl FromRequest for User {
         Box::pin(async move { token.validate(&conn).await })
     }
 }
+
+// Using the FromRequest trait to authenticate an admin
+impl FromRequest for Admin {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, ApiError>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|hv| hv.to_str().ok());
+
+        match (token, config::admin_token()) {
+            (Some(t), Some(admin_token)) if tokens_match(t, &admin_token) => ready(Ok(Admin)),
+            _ => ready(Err(ApiError::AuthenticationFailed)),
+        }
+    }
+}
+
+// Compare the tokens without stopping at the first difference, so the time
+// taken does not give away how much of the token was right
+fn tokens_match(token: &str, expected: &str) -> bool {
+    token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
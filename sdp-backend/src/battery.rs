@@ -0,0 +1,218 @@
+use crate::config;
+use crate::error::ApiError;
+use chrono::{serde::ts_seconds, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+// The most samples the battery history can be split into
+const MAX_HISTORY_SAMPLES: i64 = 2000;
+
+// A battery level reported by the robot
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatteryReading {
+    pub robot_serial_number: String,
+    #[serde(with = "ts_seconds")]
+    pub time: chrono::DateTime<Utc>,
+    pub battery_level: i64,
+    pub charging: bool,
+}
+
+// The battery levels used to decide when a robot can work, the resume level is higher
+// than the abort level, so a robot with a battery around the abort level does not keep
+// switching between the task and aborting
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatteryPolicy {
+    // The battery level must be above this to start a task
+    pub start_level: i64,
+    // A running task is aborted when the battery is at or below this
+    pub abort_level: i64,
+    // After a low battery abort the robot charges to this before working again
+    pub resume_level: i64,
+}
+
+// The battery readings in a period of time, averaged at the requested resolution
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatterySample {
+    #[serde(with = "ts_seconds")]
+    pub time: chrono::DateTime<Utc>,
+    pub battery_level: i64,
+    pub min_battery_level: i64,
+    pub max_battery_level: i64,
+    // If the robot was charging at any point in the period
+    pub charging: bool,
+}
+
+impl BatteryPolicy {
+    // Check the levels are valid percentages and are in order
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if Self::valid_battery(self.abort_level)
+            && Self::valid_battery(self.start_level)
+            && Self::valid_battery(self.resume_level)
+            && self.abort_level <= self.start_level
+            && self.start_level <= self.resume_level
+        {
+            Ok(())
+        } else {
+            Err(ApiError::InvalidBatteryPolicy)
+        }
+    }
+
+    fn valid_battery(battery_level: i64) -> bool {
+        (0..=100).contains(&battery_level)
+    }
+
+    pub fn can_start(&self, battery_level: i64) -> bool {
+        Self::valid_battery(battery_level) && battery_level > self.start_level
+    }
+
+    pub fn should_abort(&self, battery_level: i64) -> bool {
+        !Self::valid_battery(battery_level) || battery_level <= self.abort_level
+    }
+
+    pub fn can_resume(&self, battery_level: i64) -> bool {
+        Self::valid_battery(battery_level) && battery_level >= self.resume_level
+    }
+}
+
+impl Default for BatteryPolicy {
+    fn default() -> Self {
+        Self {
+            start_level: config::battery_start_level(),
+            abort_level: config::battery_abort_level(),
+            resume_level: config::battery_resume_level(),
+        }
+    }
+}
+
+impl BatteryReading {
+    // Store the reading, readings are downsampled so only one is kept per sample interval
+    // unless the robot starts or stops charging.
+    pub async fn record(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        battery_level: i64,
+        charging: bool,
+    ) -> Result<Option<Self>, ApiError> {
+        let time_now = chrono::Utc::now();
+        let sample_interval = Duration::seconds(config::battery_sample_interval());
+
+        match Self::latest(conn, robot_serial_number).await? {
+            Some(r) if r.charging == charging && time_now - r.time < sample_interval => {
+                return Ok(None)
+            }
+            _ => (),
+        }
+
+        sqlx::query!(
+            r#"
+INSERT INTO BatteryReadings (robot_serial_number, time, battery_level, charging)
+VALUES ($1, $2, $3, $4)
+               "#,
+            robot_serial_number,
+            time_now,
+            battery_level,
+            charging
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(Some(Self {
+            robot_serial_number: robot_serial_number.to_string(),
+            time: time_now,
+            battery_level,
+            charging,
+        }))
+    }
+
+    pub async fn latest(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Option<Self>, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT * FROM BatteryReadings B
+WHERE B.robot_serial_number = $1
+ORDER BY B.time DESC
+LIMIT 1
+               "#,
+            robot_serial_number
+        )
+        .fetch_optional(conn)
+        .await
+        .map(|reading| {
+            reading.map(|r| Self {
+                robot_serial_number: r.robot_serial_number,
+                time: r.time,
+                battery_level: r.battery_level,
+                charging: r.charging,
+            })
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // Get the battery history between the two times, with one sample for each
+    // resolution seconds that has readings
+    pub async fn history(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        from: chrono::DateTime<Utc>,
+        to: chrono::DateTime<Utc>,
+        resolution: i64,
+    ) -> Result<Vec<BatterySample>, ApiError> {
+        Self::check_range(from, to, resolution)?;
+
+        let results = sqlx::query!(
+            r#"
+SELECT to_timestamp((extract(epoch FROM B.time)::BIGINT / $4::BIGINT) * $4::BIGINT) AS "time!",
+       AVG(B.battery_level)::BIGINT AS "battery_level!",
+       MIN(B.battery_level) AS "min_battery_level!",
+       MAX(B.battery_level) AS "max_battery_level!",
+       BOOL_OR(B.charging) AS "charging!"
+FROM BatteryReadings B
+WHERE B.robot_serial_number = $1 AND
+      B.time >= $2 AND
+      B.time < $3
+GROUP BY 1
+ORDER BY 1
+               "#,
+            robot_serial_number,
+            from,
+            to,
+            resolution
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        let mut samples = Vec::new();
+        for s in results {
+            samples.push(BatterySample {
+                time: s.time,
+                battery_level: s.battery_level,
+                min_battery_level: s.min_battery_level,
+                max_battery_level: s.max_battery_level,
+                charging: s.charging,
+            });
+        }
+
+        Ok(samples)
+    }
+
+    // The range must be in order and not split into too many samples
+    pub fn check_range(
+        from: chrono::DateTime<Utc>,
+        to: chrono::DateTime<Utc>,
+        resolution: i64,
+    ) -> Result<(), ApiError> {
+        if from >= to || resolution <= 0 {
+            return Err(ApiError::InvalidTimeRange);
+        }
+
+        if (to - from).num_seconds() / resolution > MAX_HISTORY_SAMPLES {
+            return Err(ApiError::InvalidTimeRange);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use crate::error::ApiError;
+use chrono::{serde::ts_milliseconds, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+// The largest clock offset accepted, in milliseconds. Clients further out than
+// this need their clock setting rather than correcting.
+const MAX_CLOCK_OFFSET: i64 = 24 * 60 * 60 * 1000;
+
+// A client whose clock offset is being tracked
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Client {
+    User(String),
+}
+
+// The estimated difference between a clients clock and the server clock,
+// the offset is positive when the client clock is ahead of the server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ClockOffset {
+    pub client_id: String,
+    // Offset and round trip delay in milliseconds
+    pub offset: i64,
+    pub round_trip: Option<i64>,
+    #[serde(with = "ts_milliseconds")]
+    pub updated_at: chrono::DateTime<Utc>,
+}
+
+// An NTP like time request, the client sends the time it sent the request
+// and optionally the offset and round trip it calculated from the last exchange
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimeRequest {
+    #[serde(with = "ts_milliseconds")]
+    pub client_transmit: chrono::DateTime<Utc>,
+    pub offset: Option<i64>,
+    pub round_trip: Option<i64>,
+}
+
+// The client can calculate its offset from these and the time it receives the response
+//   offset = ((server_receive - client_transmit) + (server_transmit - client_receive)) / 2
+//   round_trip = (client_receive - client_transmit) - (server_transmit - server_receive)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimeResponse {
+    #[serde(with = "ts_milliseconds")]
+    pub client_transmit: chrono::DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    pub server_receive: chrono::DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    pub server_transmit: chrono::DateTime<Utc>,
+}
+
+impl std::fmt::Display for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Client::User(user_name) => write!(f, "Client::User({})", user_name),
+        }
+    }
+}
+
+impl TimeRequest {
+    // Record the offset for the client and build the response
+    pub async fn sync(
+        &self,
+        conn: &PgPool,
+        client: Option<Client>,
+        server_receive: chrono::DateTime<Utc>,
+    ) -> Result<TimeResponse, ApiError> {
+        if let Some(client) = client {
+            // Prefer the offset the client measured, otherwise estimate it from this
+            // request alone, which includes the one way network delay
+            let offset = self
+                .offset
+                .unwrap_or_else(|| (self.client_transmit - server_receive).num_milliseconds());
+            if !(-MAX_CLOCK_OFFSET..=MAX_CLOCK_OFFSET).contains(&offset) {
+                return Err(ApiError::InvalidClockOffset);
+            }
+
+            ClockOffset::set(conn, &client, offset, self.round_trip).await?;
+        }
+
+        Ok(TimeResponse {
+            client_transmit: self.client_transmit,
+            server_receive,
+            server_transmit: chrono::Utc::now(),
+        })
+    }
+}
+
+impl ClockOffset {
+    pub async fn set(
+        conn: &PgPool,
+        client: &Client,
+        offset: i64,
+        round_trip: Option<i64>,
+    ) -> Result<Self, ApiError> {
+        let client_id = client.to_string();
+        let updated_at = chrono::Utc::now();
+
+        sqlx::query!(
+            r#"
+INSERT INTO ClockOffsets (client_id, clock_offset, round_trip, updated_at)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (client_id) DO UPDATE
+SET clock_offset = $2,
+    round_trip = $3,
+    updated_at = $4
+               "#,
+            client_id,
+            offset,
+            round_trip,
+            updated_at
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(Self {
+            client_id,
+            offset,
+            round_trip,
+            updated_at,
+        })
+    }
+
+    // Get the offset for the client, clients that have never synced are assumed to be correct
+    pub async fn get(conn: &PgPool, client: &Client) -> Result<Self, ApiError> {
+        let client_id = client.to_string();
+
+        sqlx::query!(
+            r#"
+SELECT * FROM ClockOffsets C
+WHERE C.client_id = $1
+               "#,
+            client_id
+        )
+        .fetch_optional(conn)
+        .await
+        .map(|offset| match offset {
+            Some(o) => Self {
+                client_id: o.client_id,
+                offset: o.clock_offset,
+                round_trip: o.round_trip,
+                updated_at: o.updated_at,
+            },
+            None => Self {
+                client_id: client_id.clone(),
+                offset: 0,
+                round_trip: None,
+                updated_at: chrono::Utc::now(),
+            },
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // Convert a time from the clients clock to the server clock, None if the
+    // time is out of range once corrected
+    pub fn server_time(&self, client_time: chrono::DateTime<Utc>) -> Option<chrono::DateTime<Utc>> {
+        let offset = self.offset.clamp(-MAX_CLOCK_OFFSET, MAX_CLOCK_OFFSET);
+        client_time.checked_sub_signed(Duration::milliseconds(offset))
+    }
+}
@@ -1,12 +1,22 @@
-use chrono::{serde::ts_seconds, Utc};
+use crate::error::ApiError;
+use crate::firmware::FirmwareOffer;
+use chrono::{
+    serde::{ts_seconds, ts_seconds_option},
+    Utc,
+};
 use serde::{Deserialize, Serialize};
 
 mod abort;
 mod create;
+mod pause;
 mod retrieve;
+mod task;
 mod time;
 mod update;
 
+pub use task::{CleaningMode, TaskParameters};
+pub use time::TimeTolerance;
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Command {
     pub command_id: i64,
@@ -16,7 +26,19 @@ pub struct Command {
     #[serde(with = "ts_seconds")]
     time_instruction: chrono::DateTime<Utc>,
     pub instruction: Instruction,
+    // How the robot should carry out a task, None for the other instructions
+    #[serde(default)]
+    pub parameters: Option<TaskParameters>,
     pub status: Status,
+    // How many seconds late the command was dispatched to the robot
+    pub lateness: Option<i64>,
+    // If the command was cancelled by a low battery and should be resumed after charging
+    pub interrupted: bool,
+    // Why and when the command was paused, None if it is not paused
+    #[serde(default)]
+    pub pause_reason: Option<AbortReason>,
+    #[serde(default, with = "ts_seconds_option")]
+    pub paused_at: Option<chrono::DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -47,56 +69,147 @@ impl Status {
 pub enum CleaningPattern {
     ZigZag,
     Circular,
+    // Clean a circle around a point, in map coordinates
+    Spot { x: f64, y: f64, radius: f64 },
+    // Follow the perimeter of the room
+    Edge,
+    Spiral,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum AbortReason {
     LowBattery,
     Safety,
+    // The robot is blocked and can not carry on with the task
+    Obstacle,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Instruction {
     Abort(AbortReason),
+    // The parameters of the task are kept on the command next to the instruction,
+    // so robots that do not know about them still get the instruction they expect
     Task(CleaningPattern),
     Idle,
+    // Go back to the dock and charge
+    ReturnToDock,
+    // Download and install a firmware release
+    UpdateFirmware(FirmwareOffer),
 }
 
 impl std::fmt::Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use Instruction::{Abort, Idle, Task};
+        use Instruction::{Abort, Idle, ReturnToDock, Task, UpdateFirmware};
 
         match self {
-            Abort(AbortReason::LowBattery) => {
-                write!(f, "Abort(AbortReason::LowBattery)")
-            }
-            Abort(AbortReason::Safety) => write!(f, "Abort(AbortReason::Safety)"),
-            Task(CleaningPattern::Circular) => {
-                write!(f, "Task(CleaningPattern::Circular)")
-            }
-            Task(CleaningPattern::ZigZag) => {
-                write!(f, "Task(CleaningPattern::ZigZag)")
-            }
+            Abort(reason) => write!(f, "Abort({})", reason),
+            Task(pattern) => write!(f, "Task({})", pattern),
             Idle => write!(f, "Idle"),
+            ReturnToDock => write!(f, "ReturnToDock"),
+            UpdateFirmware(offer) => {
+                let offer_json = serde_json::to_string(offer).unwrap_or_default();
+                write!(f, "UpdateFirmware({})", offer_json)
+            }
         }
     }
 }
 
 impl From<String> for Instruction {
     fn from(instruction: String) -> Self {
-        use Instruction::{Abort, Idle, Task};
+        use Instruction::{Abort, Idle, ReturnToDock, Task, UpdateFirmware};
 
         match &instruction[..] {
             "Abort(AbortReason::LowBattery)" => Abort(AbortReason::LowBattery),
             "Abort(AbortReason::Safety)" => Abort(AbortReason::Safety),
-            "Task(CleaningPattern::Circular)" => Task(CleaningPattern::Circular),
-            "Task(CleaningPattern::ZigZag)" => Task(CleaningPattern::ZigZag),
+            "Abort(AbortReason::Obstacle)" => Abort(AbortReason::Obstacle),
+            t if t.starts_with("Task(") && t.ends_with(')') => t[5..t.len() - 1]
+                .parse()
+                .map(Task)
+                .unwrap_or(Abort(AbortReason::Safety)),
             "Idle" => Idle,
+            "ReturnToDock" => ReturnToDock,
+            u if u.starts_with("UpdateFirmware(") && u.ends_with(')') => {
+                serde_json::from_str(&u["UpdateFirmware(".len()..u.len() - 1])
+                    .map(UpdateFirmware)
+                    .unwrap_or(Abort(AbortReason::Safety))
+            }
             _ => Abort(AbortReason::Safety),
         }
     }
 }
 
+impl std::fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AbortReason::LowBattery => write!(f, "AbortReason::LowBattery"),
+            AbortReason::Safety => write!(f, "AbortReason::Safety"),
+            AbortReason::Obstacle => write!(f, "AbortReason::Obstacle"),
+        }
+    }
+}
+
+impl From<String> for AbortReason {
+    fn from(reason: String) -> Self {
+        match &reason[..] {
+            "AbortReason::LowBattery" => AbortReason::LowBattery,
+            "AbortReason::Obstacle" => AbortReason::Obstacle,
+            _ => AbortReason::Safety,
+        }
+    }
+}
+
+impl CleaningPattern {
+    // The names of all of the patterns, used for the patterns a robot model supports
+    pub const NAMES: [&'static str; 5] = ["ZigZag", "Circular", "Spot", "Edge", "Spiral"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CleaningPattern::ZigZag => "ZigZag",
+            CleaningPattern::Circular => "Circular",
+            CleaningPattern::Spot { .. } => "Spot",
+            CleaningPattern::Edge => "Edge",
+            CleaningPattern::Spiral => "Spiral",
+        }
+    }
+}
+
+impl std::fmt::Display for CleaningPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CleaningPattern::Spot { x, y, radius } => {
+                write!(f, "CleaningPattern::Spot({},{},{})", x, y, radius)
+            }
+            pattern => write!(f, "CleaningPattern::{}", pattern.name()),
+        }
+    }
+}
+
+impl std::str::FromStr for CleaningPattern {
+    type Err = ApiError;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        match pattern {
+            "CleaningPattern::ZigZag" => Ok(CleaningPattern::ZigZag),
+            "CleaningPattern::Circular" => Ok(CleaningPattern::Circular),
+            "CleaningPattern::Edge" => Ok(CleaningPattern::Edge),
+            "CleaningPattern::Spiral" => Ok(CleaningPattern::Spiral),
+            p if p.starts_with("CleaningPattern::Spot(") && p.ends_with(')') => {
+                let spot = p["CleaningPattern::Spot(".len()..p.len() - 1]
+                    .split(',')
+                    .map(|v| v.parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|_| ApiError::SerializationError)?;
+
+                match spot[..] {
+                    [x, y, radius] => Ok(CleaningPattern::Spot { x, y, radius }),
+                    _ => Err(ApiError::SerializationError),
+                }
+            }
+            _ => Err(ApiError::SerializationError),
+        }
+    }
+}
+
 impl std::fmt::Display for Status {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
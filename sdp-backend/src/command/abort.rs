@@ -10,6 +10,18 @@ impl Command {
     pub async fn saftey_abort(conn: &PgPool, robot_serial_number: &str) -> Result<Self, ApiError> {
         Self::cancel_all_previous_pending(conn, robot_serial_number).await?;
 
+        // After a saftey abort an interrupted task should not be resumed
+        if let Some(c) = Self::get_interrupted(conn, robot_serial_number).await? {
+            Self::update(
+                conn,
+                &Self {
+                    interrupted: false,
+                    ..c
+                },
+            )
+            .await?;
+        }
+
         let time_now = chrono::Utc::now();
         Self::new(
             conn,
@@ -17,13 +29,14 @@ impl Command {
             time_now,
             time_now,
             &Instruction::Abort(AbortReason::Safety),
+            &None,
             &Status::Pending,
         )
         .await
     }
 
-    // Mark the given command as cancelled and will make a new abort command
-    // Mark it as in progress and return it to the robot
+    // Cancel the ongoing commands and make a new command sending the robot
+    // back to its dock to charge
     pub async fn new_low_battery(
         conn: &PgPool,
         robot_serial_number: &str,
@@ -36,7 +49,8 @@ impl Command {
             robot_serial_number,
             time_now,
             time_now,
-            &Instruction::Abort(AbortReason::LowBattery),
+            &Instruction::ReturnToDock,
+            &None,
             &Status::Pending,
         )
         .await
@@ -46,6 +60,55 @@ impl Command {
         Self::new_low_battery(conn, &self.robot_serial_number).await
     }
 
+    // Cancel the running task due to low battery, marking it as interrupted
+    // so it can be resumed once the robot has charged
+    pub async fn low_battery_interrupt(&self, conn: &PgPool) -> Result<Self, ApiError> {
+        Self::update(
+            conn,
+            &Self {
+                status: Status::Cancelled,
+                interrupted: true,
+                ..self.clone()
+            },
+        )
+        .await?;
+
+        Self::new_low_battery(conn, &self.robot_serial_number).await
+    }
+
+    // Make a new command to carry on with the interrupted task, if there is one
+    pub async fn resume_interrupted(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Option<Self>, ApiError> {
+        let interrupted = match Self::get_interrupted(conn, robot_serial_number).await? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        Self::update(
+            conn,
+            &Self {
+                interrupted: false,
+                ..interrupted.clone()
+            },
+        )
+        .await?;
+
+        let time_now = chrono::Utc::now();
+        Self::new(
+            conn,
+            robot_serial_number,
+            time_now,
+            time_now,
+            &interrupted.instruction,
+            &interrupted.parameters,
+            &Status::Pending,
+        )
+        .await
+        .map(Some)
+    }
+
     // Cancel all of the pending commands set to run before the abort is made
     pub async fn cancel_all_previous_pending(
         conn: &PgPool,
@@ -1,6 +1,10 @@
 use crate::command::Command;
-use crate::command::{AbortReason, Instruction, Status};
+use crate::command::{AbortReason, Instruction, Status, TaskParameters};
 use crate::error::ApiError;
+use crate::event::{CommandStatusChange, Event, RobotEvent};
+use crate::firmware::FirmwareOffer;
+use crate::robot::Robot;
+use crate::zone::Zone;
 use chrono::Utc;
 use sqlx::postgres::PgPool;
 
@@ -11,39 +15,65 @@ impl Command {
         time_issued: chrono::DateTime<Utc>,
         time_instruction: chrono::DateTime<Utc>,
         instruction: &Instruction,
+        parameters: &Option<TaskParameters>,
         status: &Status,
     ) -> Result<Self, ApiError> {
-        // Check the times of the command, returning an error if required
-        match Self::check_time(time_issued, time_instruction) {
-            (Some(e), None) | (None, Some(e)) => return Err(e),
+        // Check the times of the command against the robots tolerances,
+        // returning an error if required
+        let tolerance = Robot::get_by_serial(conn, robot_serial_number)
+            .await?
+            .time_tolerance();
+        match Self::check_time(time_issued, time_instruction, &tolerance) {
+            (Some(e), _) | (None, Some(e)) => return Err(e),
             _no_errors => (),
         }
 
-        sqlx::query!(
+        let command = sqlx::query!(
             r#"
-        INSERT INTO Commands (robot_serial_number, time_issued, time_instruction, instruction, status)
-        VALUES ( $1, $2, $3, $4, $5)
+        INSERT INTO Commands (robot_serial_number, time_issued, time_instruction, instruction, parameters, status)
+        VALUES ( $1, $2, $3, $4, $5, $6)
         RETURNING command_id
                 "#,
             robot_serial_number,
             time_issued,
             time_instruction,
             instruction.to_string(),
+            parameters.as_ref().map(|p| p.to_string()),
             status.to_string()
         )
         .fetch_one(conn)
         .await
         .map_err(|_| ApiError::DatabaseConnFailed)
         .map( |c|
-            Ok(Self {
+            Self {
                 command_id: c.command_id,
                 robot_serial_number: robot_serial_number.to_string(),
                 time_issued,
                 time_instruction,
                 instruction: instruction.clone(),
+                parameters: parameters.clone(),
                 status: status.clone(),
-            })
-        )?
+                lateness: None,
+                interrupted: false,
+                pause_reason: None,
+                paused_at: None,
+            }
+        )?;
+
+        // A new command is a change to the robot's commands, this wakes a waiting poll
+        Event::record(
+            conn,
+            robot_serial_number,
+            Utc::now(),
+            &RobotEvent::CommandStatus(CommandStatusChange {
+                command_id: command.command_id,
+                status: command.status.clone(),
+                instruction: Some(command.instruction.clone()),
+            }),
+        )
+        .await?;
+
+        Ok(command)
     }
 
     pub async fn new_idle(conn: &PgPool, robot_serial_number: &str) -> Result<Self, ApiError> {
@@ -54,6 +84,25 @@ impl Command {
             time_now,
             time_now,
             &Instruction::Idle,
+            &None,
+            &Status::Pending,
+        )
+        .await
+    }
+
+    pub async fn new_firmware_update(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        offer: FirmwareOffer,
+    ) -> Result<Self, ApiError> {
+        let time_now = chrono::Utc::now();
+        Self::new(
+            conn,
+            robot_serial_number,
+            time_now,
+            time_now,
+            &Instruction::UpdateFirmware(offer),
+            &None,
             &Status::Pending,
         )
         .await
@@ -65,7 +114,25 @@ impl Command {
         time_issued: chrono::DateTime<Utc>,
         time_instruction: Vec<chrono::DateTime<Utc>>,
         instruction: &Instruction,
+        parameters: &Option<TaskParameters>,
     ) -> Result<Vec<Command>, ApiError> {
+        // Firmware updates and returning to the dock are only started by the server
+        if matches!(
+            instruction,
+            Instruction::UpdateFirmware(_) | Instruction::ReturnToDock
+        ) {
+            return Err(ApiError::CmdInstructionNotSupported);
+        }
+
+        // Check the instruction is valid, filling in the default task parameters,
+        // and that the robot is able to run it
+        let parameters = &instruction.validated_parameters(parameters)?;
+        Robot::get_by_serial(conn, robot_serial_number)
+            .await?
+            .check_instruction(conn, instruction, parameters)
+            .await?;
+        Zone::check_rooms(conn, robot_serial_number, parameters).await?;
+
         // Check if the command is a SafetyAbort
         if instruction == &Instruction::Abort(AbortReason::Safety) {
             return Ok(vec![
@@ -81,6 +148,7 @@ impl Command {
                 time_issued,
                 t,
                 instruction,
+                parameters,
                 &Status::Pending,
             )
             .await?;
@@ -0,0 +1,85 @@
+use crate::command::Command;
+use crate::command::{AbortReason, Status};
+use crate::config;
+use crate::error::ApiError;
+use crate::event::{Event, RobotEvent};
+use chrono::Duration;
+use sqlx::postgres::PgPool;
+
+impl Command {
+    pub fn paused_by_obstacle(&self) -> bool {
+        self.status == Status::Paused && self.pause_reason == Some(AbortReason::Obstacle)
+    }
+
+    // If the command has been blocked by an obstacle for longer than the obstacle timeout
+    pub fn obstacle_timed_out(&self) -> bool {
+        let timeout = Duration::seconds(config::obstacle_timeout());
+
+        match self.paused_at {
+            Some(t) if self.paused_by_obstacle() => chrono::Utc::now() - t > timeout,
+            _ => false,
+        }
+    }
+
+    // Pause the command while the robot is blocked, letting the user know the first time
+    pub async fn obstacle_pause(&self, conn: &PgPool) -> Result<Self, ApiError> {
+        if self.paused_by_obstacle() {
+            return Ok(self.clone());
+        }
+
+        let time_now = chrono::Utc::now();
+        Event::record(
+            conn,
+            &self.robot_serial_number,
+            time_now,
+            &RobotEvent::ObstaclePaused(self.command_id),
+        )
+        .await?;
+
+        Self::update(
+            conn,
+            &Self {
+                status: Status::Paused,
+                pause_reason: Some(AbortReason::Obstacle),
+                paused_at: Some(time_now),
+                ..self.clone()
+            },
+        )
+        .await
+    }
+
+    // Carry on with the command once the obstacle has been cleared
+    pub async fn obstacle_cleared(&self, conn: &PgPool) -> Result<Self, ApiError> {
+        Event::record(
+            conn,
+            &self.robot_serial_number,
+            chrono::Utc::now(),
+            &RobotEvent::ObstacleCleared(self.command_id),
+        )
+        .await?;
+
+        Self::update(
+            conn,
+            &Self {
+                status: Status::InProgress,
+                pause_reason: None,
+                paused_at: None,
+                ..self.clone()
+            },
+        )
+        .await
+    }
+
+    // Cancel the command as the obstacle was not cleared in time
+    pub async fn obstacle_cancel(&self, conn: &PgPool) -> Result<Self, ApiError> {
+        Event::record(
+            conn,
+            &self.robot_serial_number,
+            chrono::Utc::now(),
+            &RobotEvent::ObstacleTimeout(self.command_id),
+        )
+        .await?;
+
+        self.cancel(conn).await
+    }
+}
@@ -1,5 +1,6 @@
 use crate::command::Command;
 use crate::error::ApiError;
+use crate::robot::Robot;
 use sqlx::postgres::PgPool;
 
 impl Command {
@@ -19,7 +20,12 @@ WHERE C.command_id = $1
             time_issued: c.time_issued,
             time_instruction: c.time_instruction,
             instruction: c.instruction.into(),
+            parameters: c.parameters.and_then(|p| p.parse().ok()),
             status: c.status.into(),
+            lateness: c.lateness,
+            interrupted: c.interrupted,
+            pause_reason: c.pause_reason.map(|r| r.into()),
+            paused_at: c.paused_at,
         })
         .map_err(|_| ApiError::DatabaseConnFailed)
     }
@@ -49,56 +55,76 @@ WHERE C.robot_serial_number = $1 AND
                 time_issued: c.time_issued,
                 time_instruction: c.time_instruction,
                 instruction: c.instruction.into(),
+                parameters: c.parameters.and_then(|p| p.parse().ok()),
                 status: c.status.into(),
+                lateness: c.lateness,
+                interrupted: c.interrupted,
+                pause_reason: c.pause_reason.map(|r| r.into()),
+                paused_at: c.paused_at,
             })
         }
 
         Ok(commands)
     }
 
-    // Cancel all of the commands that do not call in the
-    async fn prune(conn: &PgPool, commands: Vec<Self>) -> Result<Vec<Self>, ApiError> {
+    // Cancel all of the commands that do not call in the grace period,
+    // or that the robot is not able to run
+    async fn prune(
+        conn: &PgPool,
+        commands: Vec<Self>,
+        robot: &Robot,
+    ) -> Result<Vec<Self>, ApiError> {
         let mut pruned_commands = Vec::new();
+        let tolerance = robot.time_tolerance();
         let time_now = chrono::Utc::now();
         for c in commands {
+            if robot
+                .check_instruction(conn, &c.instruction, &c.parameters)
+                .await
+                .is_err()
+            {
+                c.cancel(conn).await?;
+                continue;
+            }
+
             // Check for errors in the time instruction
-            let (_, time_instruction_error) = c.valid_time();
+            let (_, time_instruction_error) = c.valid_time(&tolerance);
 
             match time_instruction_error {
                 // If the command is scheduled for the future that is fine
                 _ if c.time_instruction > time_now => pruned_commands.push(c),
 
-                // If the command is inside the time buffer, its valid
+                // If the command is inside the grace period, its valid
                 None => pruned_commands.push(c),
 
                 // There was an error with the command, cancel it
                 Some(_) => {
                     c.cancel(conn).await?;
-                    ()
                 }
             }
         }
 
-        pruned_commands.sort_by(|a, b| a.time_instruction.cmp(&b.time_instruction));
+        pruned_commands.sort_by_key(|c| c.time_instruction);
 
         Ok(pruned_commands)
     }
 
     // Returns the command that should be executed next, or None if isn't anything
     // Prune the commands, if the time has expired and they have not been completed mark them as cancelled
-    // Check that the command is in the time buffer before sending it.
+    // Check that the command is in the grace period before sending it.
     pub async fn pending(
         conn: &PgPool,
         robot_serial_number: &str,
     ) -> Result<Option<Self>, ApiError> {
         // Get all of the pending commands for this robot
         let all_pending_commands = Self::get_all_pending(conn, robot_serial_number).await?;
+        let robot = Robot::get_by_serial(conn, robot_serial_number).await?;
 
         // Cancel all of the invalid commands and get the earliest one
-        let commands = Self::prune(conn, all_pending_commands).await?;
+        let commands = Self::prune(conn, all_pending_commands, &robot).await?;
 
-        match commands.get(0) {
-            Some(c) if c.instruction_in_buffer() => {
+        match commands.first() {
+            Some(c) if c.instruction_in_buffer(&robot.time_tolerance()) => {
                 println!("{:?}", c);
                 Ok(Some(c.clone()))
             }
@@ -129,13 +155,53 @@ WHERE C.robot_serial_number = $1 AND
                 time_issued: c.time_issued,
                 time_instruction: c.time_instruction,
                 instruction: c.instruction.into(),
+                parameters: c.parameters.and_then(|p| p.parse().ok()),
                 status: c.status.into(),
+                lateness: c.lateness,
+                interrupted: c.interrupted,
+                pause_reason: c.pause_reason.map(|r| r.into()),
+                paused_at: c.paused_at,
             })
         }
 
         Ok(all_ongoing_commands)
     }
 
+    // Get the most recent task that was interrupted by a low battery, if there is one
+    pub async fn get_interrupted(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Option<Self>, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT * FROM Commands C
+WHERE C.robot_serial_number = $1 AND
+      C.interrupted
+ORDER BY C.command_id DESC
+LIMIT 1
+               "#,
+            robot_serial_number
+        )
+        .fetch_optional(conn)
+        .await
+        .map(|command| {
+            command.map(|c| Self {
+                command_id: c.command_id,
+                robot_serial_number: c.robot_serial_number,
+                time_issued: c.time_issued,
+                time_instruction: c.time_instruction,
+                instruction: c.instruction.into(),
+                parameters: c.parameters.and_then(|p| p.parse().ok()),
+                status: c.status.into(),
+                lateness: c.lateness,
+                interrupted: c.interrupted,
+                pause_reason: c.pause_reason.map(|r| r.into()),
+                paused_at: c.paused_at,
+            })
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
     pub async fn cancel_all_ongoing(
         conn: &PgPool,
         robot_serial_number: &str,
@@ -171,7 +237,7 @@ WHERE C.robot_serial_number = $1 AND
 SELECT * FROM Commands C
 WHERE C.robot_serial_number = $1
                "#,
-            robot_serial_number.clone(),
+            robot_serial_number,
         )
         .fetch_all(conn)
         .await
@@ -187,7 +253,12 @@ WHERE C.robot_serial_number = $1
                 time_issued: r.time_issued,
                 time_instruction: r.time_instruction,
                 instruction: r.instruction.into(),
+                parameters: r.parameters.and_then(|p| p.parse().ok()),
                 status: r.status.into(),
+                lateness: r.lateness,
+                interrupted: r.interrupted,
+                pause_reason: r.pause_reason.map(|r| r.into()),
+                paused_at: r.paused_at,
             });
         }
         Ok(commands)
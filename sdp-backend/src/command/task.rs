@@ -0,0 +1,204 @@
+use crate::command::{CleaningPattern, Instruction};
+use crate::error::ApiError;
+use serde::{Deserialize, Serialize};
+
+// Limits for the task parameters
+const MAX_PASSES: i64 = 3;
+const MAX_DURATION_LIMIT: i64 = 240;
+const MAX_SPOT_RADIUS: f64 = 3.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum CleaningMode {
+    Vacuum,
+    Mop,
+    VacuumAndMop,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SuctionPower {
+    Low,
+    Medium,
+    High,
+    Max,
+}
+
+// How the robot should carry out a task. Parameters that are not given
+// when the command is made are filled in with the defaults for the pattern.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TaskParameters {
+    #[serde(default)]
+    pub mode: Option<CleaningMode>,
+    #[serde(default)]
+    pub suction_power: Option<SuctionPower>,
+    // The number of times to go over the area
+    #[serde(default)]
+    pub passes: Option<i64>,
+    // The maximum number of minutes the task can take, None for no limit
+    #[serde(default)]
+    pub duration_limit: Option<i64>,
+    // The ids of the room zones to clean, None to clean everywhere
+    #[serde(default)]
+    pub rooms: Option<Vec<i64>>,
+}
+
+impl TaskParameters {
+    // The defaults for each of the cleaning patterns
+    pub fn defaults(pattern: &CleaningPattern) -> Self {
+        match pattern {
+            CleaningPattern::ZigZag => Self {
+                mode: Some(CleaningMode::Vacuum),
+                suction_power: Some(SuctionPower::Medium),
+                passes: Some(1),
+                duration_limit: None,
+                rooms: None,
+            },
+            CleaningPattern::Circular => Self {
+                mode: Some(CleaningMode::Vacuum),
+                suction_power: Some(SuctionPower::High),
+                passes: Some(2),
+                duration_limit: None,
+                rooms: None,
+            },
+            CleaningPattern::Spot { .. } => Self {
+                mode: Some(CleaningMode::Vacuum),
+                suction_power: Some(SuctionPower::Max),
+                passes: Some(2),
+                duration_limit: Some(15),
+                rooms: None,
+            },
+            CleaningPattern::Edge => Self {
+                mode: Some(CleaningMode::Vacuum),
+                suction_power: Some(SuctionPower::High),
+                passes: Some(1),
+                duration_limit: None,
+                rooms: None,
+            },
+            CleaningPattern::Spiral => Self {
+                mode: Some(CleaningMode::Vacuum),
+                suction_power: Some(SuctionPower::Medium),
+                passes: Some(1),
+                duration_limit: Some(30),
+                rooms: None,
+            },
+        }
+    }
+
+    // Fill in any parameters that were not given with the defaults for the pattern,
+    // mopping does not use suction so it is only filled in for the other modes
+    pub fn with_defaults(&self, pattern: &CleaningPattern) -> Self {
+        let defaults = Self::defaults(pattern);
+        let mode = self.mode.clone().or(defaults.mode);
+        let suction_power = match mode {
+            Some(CleaningMode::Mop) => self.suction_power.clone(),
+            _ => self.suction_power.clone().or(defaults.suction_power),
+        };
+
+        Self {
+            mode,
+            suction_power,
+            passes: self.passes.or(defaults.passes),
+            duration_limit: self.duration_limit.or(defaults.duration_limit),
+            rooms: self.rooms.clone(),
+        }
+    }
+
+    pub fn validate(&self, pattern: &CleaningPattern) -> Result<(), ApiError> {
+        let params = self.with_defaults(pattern);
+
+        let valid_passes = matches!(params.passes, Some(p) if (1..=MAX_PASSES).contains(&p));
+        let valid_duration = match params.duration_limit {
+            Some(d) => (1..=MAX_DURATION_LIMIT).contains(&d),
+            None => true,
+        };
+        // Mopping does not use suction
+        let valid_suction =
+            !(params.mode == Some(CleaningMode::Mop) && params.suction_power.is_some());
+        let valid_rooms = match &params.rooms {
+            Some(rooms) => !rooms.is_empty(),
+            None => true,
+        };
+
+        if valid_passes && valid_duration && valid_suction && valid_rooms {
+            Ok(())
+        } else {
+            Err(ApiError::InvalidTaskParameters)
+        }
+    }
+}
+
+impl CleaningPattern {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        match self {
+            CleaningPattern::Spot { x, y, radius } => {
+                let valid_centre = x.is_finite() && y.is_finite();
+                let valid_radius = *radius > 0.0 && *radius <= MAX_SPOT_RADIUS;
+
+                if valid_centre && valid_radius {
+                    Ok(())
+                } else {
+                    Err(ApiError::InvalidTaskParameters)
+                }
+            }
+            _valid => Ok(()),
+        }
+    }
+}
+
+impl Instruction {
+    // Check the instruction is valid and fill in the default parameters for tasks,
+    // the other instructions do not take any parameters
+    pub fn validated_parameters(
+        &self,
+        parameters: &Option<TaskParameters>,
+    ) -> Result<Option<TaskParameters>, ApiError> {
+        match self {
+            Instruction::Task(pattern) => {
+                let parameters = parameters.clone().unwrap_or_default();
+                pattern.validate()?;
+                parameters.validate(pattern)?;
+                Ok(Some(parameters.with_defaults(pattern)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // The capabilities a robot must have to run the instruction, the built in
+    // instructions can be run by every robot
+    pub fn required_capabilities(&self, parameters: &Option<TaskParameters>) -> Vec<&'static str> {
+        match self {
+            Instruction::Task(pattern) => {
+                let mut capabilities = vec![pattern.name()];
+                if let Some(parameters) = parameters {
+                    if let Some(CleaningMode::Mop) | Some(CleaningMode::VacuumAndMop) =
+                        parameters.mode
+                    {
+                        capabilities.push("Mop");
+                    }
+                    if parameters.rooms.is_some() {
+                        capabilities.push("Rooms");
+                    }
+                }
+                capabilities
+            }
+            Instruction::Abort(_)
+            | Instruction::Idle
+            | Instruction::ReturnToDock
+            | Instruction::UpdateFirmware(_) => Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for TaskParameters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parameters_json = serde_json::to_string(&self).unwrap_or_default();
+        write!(f, "{}", parameters_json)
+    }
+}
+
+impl std::str::FromStr for TaskParameters {
+    type Err = ApiError;
+
+    fn from_str(parameters: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(parameters).map_err(|_| ApiError::SerializationError)
+    }
+}
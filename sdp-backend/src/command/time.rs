@@ -2,47 +2,95 @@ use crate::command::Command;
 use crate::error::ApiError;
 use chrono::{Duration, Utc};
 
-// TODO: Set this to a sensible value
-const TIME_BUFFER: i64 = 5;
+// The largest tolerance a robot can be given, in seconds
+const MAX_TOLERANCE: i64 = 24 * 60 * 60;
+
+// The time windows used when validating and dispatching commands for a robot.
+// The global values come from the config, and can be overwritten per robot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeTolerance {
+    // How far the time issued can differ from the server time
+    pub time_issued: Duration,
+    // How long after the time instruction the command can still be dispatched
+    pub grace_period: Duration,
+}
+
+impl TimeTolerance {
+    // A robot specific tolerance in seconds can not be negative, and is capped at a day
+    pub fn check_override(seconds: Option<i64>) -> Result<(), ApiError> {
+        match seconds {
+            Some(s) if !(0..=MAX_TOLERANCE).contains(&s) => Err(ApiError::InvalidTimeTolerance),
+            _ => Ok(()),
+        }
+    }
+}
 
 impl Command {
-    pub fn valid_time(&self) -> (Option<ApiError>, Option<ApiError>) {
-        Self::check_time(self.time_issued, self.time_instruction)
+    pub fn valid_time(&self, tolerance: &TimeTolerance) -> (Option<ApiError>, Option<ApiError>) {
+        Self::check_time(self.time_issued, self.time_instruction, tolerance)
     }
 
+    // The times should already be corrected for the skew of the clients clock,
+    // see ClockOffset::server_time
     pub fn check_time(
         time_issued: chrono::DateTime<Utc>,
         time_instruction: chrono::DateTime<Utc>,
+        tolerance: &TimeTolerance,
     ) -> (Option<ApiError>, Option<ApiError>) {
         let time_now = chrono::Utc::now();
-        let time_period = Duration::seconds(TIME_BUFFER);
 
-        // Check that the time the command was made was inside the time buffer
+        // Check that the time the command was made was inside the skew tolerance
         // The command could have been issued a little before or after
-        let valid_time_issued = (time_now - time_period)..(time_now + time_period);
+        let valid_time_issued =
+            (time_now - tolerance.time_issued)..(time_now + tolerance.time_issued);
         let time_issued_error = if !valid_time_issued.contains(&time_issued) {
             Some(ApiError::InvalidTimeIssued)
         } else {
             None
         };
 
-        // Check that the command is to be executed in the future or within the last time buffer
-        let valid_time_instruction = (time_now - time_period)..time_now;
-        let time_instruction_error =
-            if !valid_time_instruction.contains(&time_instruction) || time_instruction > time_now {
-                Some(ApiError::InvalidTimeInstruction)
-            } else {
-                None
-            };
+        // Check that the command is to be executed in the future or within the grace period
+        let time_instruction_error = if time_instruction < time_now - tolerance.grace_period {
+            Some(ApiError::InvalidTimeInstruction)
+        } else {
+            None
+        };
 
         (time_issued_error, time_instruction_error)
     }
 
-    pub fn instruction_in_buffer(&self) -> bool {
+    pub fn instruction_in_buffer(&self, tolerance: &TimeTolerance) -> bool {
         let time_now = chrono::Utc::now();
-        let time_period = Duration::seconds(TIME_BUFFER);
 
-        let valid_time_instruction = (time_now - time_period)..time_now;
+        let valid_time_instruction = (time_now - tolerance.grace_period)..time_now;
         valid_time_instruction.contains(&self.time_instruction)
     }
+
+    // The number of seconds after the time instruction the command is being dispatched,
+    // None if the command is on time
+    pub fn dispatch_lateness(&self) -> Option<i64> {
+        let late_by = (chrono::Utc::now() - self.time_instruction).num_seconds();
+
+        if late_by > 0 {
+            Some(late_by)
+        } else {
+            None
+        }
+    }
+
+    // The number of seconds since the command was dispatched to the robot
+    pub fn time_since_dispatch(&self) -> i64 {
+        let dispatched_at = self.time_instruction + Duration::seconds(self.lateness.unwrap_or(0));
+
+        (chrono::Utc::now() - dispatched_at).num_seconds().max(0)
+    }
+}
+
+impl Default for TimeTolerance {
+    fn default() -> Self {
+        Self {
+            time_issued: Duration::seconds(crate::config::time_issued_tolerance()),
+            grace_period: Duration::seconds(crate::config::instruction_grace_period()),
+        }
+    }
 }
@@ -1,47 +1,30 @@
 use crate::command::Command;
-use crate::command::{Instruction, Status};
+use crate::command::Status;
 use crate::error::ApiError;
-use chrono::Utc;
+use crate::event::{CommandStatusChange, Event, RobotEvent};
 use sqlx::postgres::PgPool;
 
 impl Command {
     pub async fn completed(&self, conn: &PgPool) -> Result<Self, ApiError> {
-        Self::update(
-            conn,
-            self.command_id,
-            &self.robot_serial_number,
-            self.time_issued,
-            self.time_instruction,
-            self.instruction.clone(),
-            Status::Completed,
-        )
-        .await
+        self.update_status(conn, &Status::Completed).await
     }
 
+    // Dispatch the command to the robot, recording how late it was
+    // if this is the first time it has been dispatched
     pub async fn in_progress(&self, conn: &PgPool) -> Result<Self, ApiError> {
         Self::update(
             conn,
-            self.command_id,
-            &self.robot_serial_number,
-            self.time_issued,
-            self.time_instruction,
-            self.instruction.clone(),
-            Status::InProgress,
+            &Self {
+                status: Status::InProgress,
+                lateness: self.lateness.or_else(|| self.dispatch_lateness()),
+                ..self.clone()
+            },
         )
         .await
     }
 
     pub async fn cancel(&self, conn: &PgPool) -> Result<Self, ApiError> {
-        Self::update(
-            conn,
-            self.command_id,
-            &self.robot_serial_number,
-            self.time_issued,
-            self.time_instruction,
-            self.instruction.clone(),
-            Status::Cancelled,
-        )
-        .await
+        self.update_status(conn, &Status::Cancelled).await
     }
 
     // Updates the given command with the new status
@@ -52,47 +35,97 @@ impl Command {
     ) -> Result<Self, ApiError> {
         Self::update(
             conn,
-            self.command_id,
-            &self.robot_serial_number,
-            self.time_issued,
-            self.time_instruction,
-            self.instruction.clone(),
-            new_status.clone(),
+            &Self {
+                status: new_status.clone(),
+                ..self.clone()
+            },
+        )
+        .await
+    }
+
+    // Updates the given command with the new status, also returning if the status changed
+    pub async fn update_status_changed(
+        &self,
+        conn: &PgPool,
+        new_status: &Status,
+    ) -> Result<(Self, bool), ApiError> {
+        Self::update_changed(
+            conn,
+            &Self {
+                status: new_status.clone(),
+                ..self.clone()
+            },
         )
         .await
     }
 
-    pub async fn update(
+    // Write all of the fields of the given command to the database
+    pub async fn update(conn: &PgPool, command: &Self) -> Result<Command, ApiError> {
+        Self::update_changed(conn, command)
+            .await
+            .map(|(command, _)| command)
+    }
+
+    // Write all of the fields of the given command to the database, also returning if
+    // this changed the status. The row is locked while it is read, so when the same
+    // status is written twice at once only one of them reports the change.
+    pub async fn update_changed(
         conn: &PgPool,
-        command_id: i64,
-        robot_serial_number: &str,
-        time_issued: chrono::DateTime<Utc>,
-        time_instruction: chrono::DateTime<Utc>,
-        instruction: Instruction,
-        status: Status,
-    ) -> Result<Command, ApiError> {
-        sqlx::query!(
+        command: &Self,
+    ) -> Result<(Command, bool), ApiError> {
+        let previous_status: Status = sqlx::query!(
             r#"
         UPDATE Commands C
         SET robot_serial_number = $1,
         time_issued = $2,
         time_instruction = $3,
         instruction = $4,
-        status = $5
-        WHERE C.command_id = $6
-
+        parameters = $5,
+        status = $6,
+        lateness = $7,
+        interrupted = $8,
+        pause_reason = $9,
+        paused_at = $10
+        FROM (SELECT status FROM Commands WHERE command_id = $11 FOR UPDATE) P
+        WHERE C.command_id = $11
+        RETURNING P.status AS previous_status
                         "#,
-            robot_serial_number,
-            time_issued,
-            time_instruction,
-            instruction.to_string(),
-            status.to_string(),
-            command_id,
+            command.robot_serial_number,
+            command.time_issued,
+            command.time_instruction,
+            command.instruction.to_string(),
+            command.parameters.as_ref().map(|p| p.to_string()),
+            command.status.to_string(),
+            command.lateness,
+            command.interrupted,
+            command.pause_reason.as_ref().map(|r| r.to_string()),
+            command.paused_at,
+            command.command_id,
         )
-        .execute(conn)
+        .fetch_one(conn)
         .await
-        .map_err(|_| ApiError::DatabaseConnFailed)?;
+        .map_err(|_| ApiError::DatabaseConnFailed)?
+        .previous_status
+        .into();
+        let changed = previous_status != command.status;
+
+        // Let the app know the command has moved on
+        if changed {
+            Event::record(
+                conn,
+                &command.robot_serial_number,
+                chrono::Utc::now(),
+                &RobotEvent::CommandStatus(CommandStatusChange {
+                    command_id: command.command_id,
+                    status: command.status.clone(),
+                    instruction: Some(command.instruction.clone()),
+                }),
+            )
+            .await?;
+        }
 
-        Self::get_by_id(conn, command_id).await
+        Self::get_by_id(conn, command.command_id)
+            .await
+            .map(|c| (c, changed))
     }
 }
@@ -0,0 +1,200 @@
+use std::env;
+
+// Default values for the backend configuration, each of these can be
+// overwritten by an envirnment variable of the same name.
+const TIME_ISSUED_TOLERANCE: i64 = 5;
+const INSTRUCTION_GRACE_PERIOD: i64 = 60;
+const ROBOT_OFFLINE_THRESHOLD: i64 = 30;
+const BATTERY_SAMPLE_INTERVAL: i64 = 60;
+const BATTERY_START_LEVEL: i64 = 50;
+const BATTERY_ABORT_LEVEL: i64 = 50;
+const BATTERY_RESUME_LEVEL: i64 = 80;
+const OBSTACLE_TIMEOUT: i64 = 300;
+const MAIN_BRUSH_LIFETIME: i64 = 300;
+const SIDE_BRUSH_LIFETIME: i64 = 200;
+const FILTER_LIFETIME: i64 = 150;
+const MOP_PAD_LIFETIME: i64 = 100;
+const MAINTENANCE_DUE_PERCENT: i64 = 10;
+const STORAGE_DIR: &str = "./storage";
+const SIGNING_KEY_FILE: &str = "./keys/command-signing.key";
+const UPLOAD_MAX_SIZE: i64 = 64 * 1024 * 1024;
+const EVENT_REPLAY_WINDOW: i64 = 3600;
+const LONG_POLL_MAX_WAIT: i64 = 20;
+const LEGACY_ROBOT_ROUTES: bool = true;
+const COMMAND_SIGNATURE_TTL: i64 = 300;
+const WEBHOOK_MAX_ATTEMPTS: i64 = 8;
+const WEBHOOK_RETRY_DELAY: i64 = 10;
+const WEBHOOK_TIMEOUT: i64 = 10;
+#[cfg(feature = "mqtt")]
+const MQTT_PORT: i64 = 1883;
+
+// Read an integer setting from the envirnment, falling back to the default
+// if it is not set or can not be parsed.
+fn env_or(key: &str, default: i64) -> i64 {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(default)
+}
+
+// Read a true or false setting from the envirnment, falling back to the default
+// if it is not set or can not be parsed.
+fn env_flag(key: &str, default: bool) -> bool {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse::<bool>().ok())
+        .unwrap_or(default)
+}
+
+/// How many seconds the time a command was issued may differ from the server time
+pub fn time_issued_tolerance() -> i64 {
+    env_or("TIME_ISSUED_TOLERANCE", TIME_ISSUED_TOLERANCE)
+}
+
+/// How many seconds after its scheduled time a command may still be sent to the robot
+pub fn instruction_grace_period() -> i64 {
+    env_or("INSTRUCTION_GRACE_PERIOD", INSTRUCTION_GRACE_PERIOD)
+}
+
+/// How many seconds without a poll before the robot is considered offline
+pub fn robot_offline_threshold() -> i64 {
+    env_or("ROBOT_OFFLINE_THRESHOLD", ROBOT_OFFLINE_THRESHOLD)
+}
+
+/// The minimum number of seconds between stored battery readings for a robot
+pub fn battery_sample_interval() -> i64 {
+    env_or("BATTERY_SAMPLE_INTERVAL", BATTERY_SAMPLE_INTERVAL)
+}
+
+/// The battery level a robot must be above to start a task
+pub fn battery_start_level() -> i64 {
+    env_or("BATTERY_START_LEVEL", BATTERY_START_LEVEL)
+}
+
+/// The battery level at or below which a running task is aborted
+pub fn battery_abort_level() -> i64 {
+    env_or("BATTERY_ABORT_LEVEL", BATTERY_ABORT_LEVEL)
+}
+
+/// The battery level a robot must charge to before an interrupted task is resumed
+pub fn battery_resume_level() -> i64 {
+    env_or("BATTERY_RESUME_LEVEL", BATTERY_RESUME_LEVEL)
+}
+
+/// How many seconds a task can be paused by an obstacle before it is cancelled
+pub fn obstacle_timeout() -> i64 {
+    env_or("OBSTACLE_TIMEOUT", OBSTACLE_TIMEOUT)
+}
+
+/// How many hours of cleaning the main brush lasts
+pub fn main_brush_lifetime() -> i64 {
+    env_or("MAIN_BRUSH_LIFETIME", MAIN_BRUSH_LIFETIME)
+}
+
+/// How many hours of cleaning the side brush lasts
+pub fn side_brush_lifetime() -> i64 {
+    env_or("SIDE_BRUSH_LIFETIME", SIDE_BRUSH_LIFETIME)
+}
+
+/// How many hours of cleaning the filter lasts
+pub fn filter_lifetime() -> i64 {
+    env_or("FILTER_LIFETIME", FILTER_LIFETIME)
+}
+
+/// How many hours of mopping the mop pad lasts
+pub fn mop_pad_lifetime() -> i64 {
+    env_or("MOP_PAD_LIFETIME", MOP_PAD_LIFETIME)
+}
+
+/// The remaining life percentage at which a component is due to be replaced
+pub fn maintenance_due_percent() -> i64 {
+    env_or("MAINTENANCE_DUE_PERCENT", MAINTENANCE_DUE_PERCENT)
+}
+
+/// The directory uploaded files, such as firmware and maps, are stored in
+pub fn storage_dir() -> String {
+    env::var("STORAGE_DIR").unwrap_or_else(|_| STORAGE_DIR.to_string())
+}
+
+/// The largest file that can be uploaded, in bytes
+pub fn upload_max_size() -> usize {
+    env_or("UPLOAD_MAX_SIZE", UPLOAD_MAX_SIZE) as usize
+}
+
+/// The token that must be given in the Authorization header of the admin routes that change
+/// the robot settings or the firmware. If this is not set those routes refuse every request.
+pub fn admin_token() -> Option<String> {
+    env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty())
+}
+
+/// How many seconds back events are replayed to a client resuming an event stream
+pub fn event_replay_window() -> i64 {
+    env_or("EVENT_REPLAY_WINDOW", EVENT_REPLAY_WINDOW)
+}
+
+/// The longest a robot poll is held open waiting for its command to change, in seconds.
+/// This should be below the offline threshold so waiting robots are not marked offline.
+pub fn long_poll_max_wait() -> i64 {
+    env_or("LONG_POLL_MAX_WAIT", LONG_POLL_MAX_WAIT)
+}
+
+/// The Ed25519 key commands are signed with, as the base64 of the 32 byte secret key.
+/// If this is not set a key is made and kept in the signing key file.
+pub fn signing_key() -> Option<String> {
+    env::var("SIGNING_KEY").ok()
+}
+
+/// The file the signing key is kept in when SIGNING_KEY is not set. This is kept apart
+/// from the storage directory, as the files in it are served over the API.
+pub fn signing_key_file() -> String {
+    env::var("SIGNING_KEY_FILE").unwrap_or_else(|_| SIGNING_KEY_FILE.to_string())
+}
+
+/// How many seconds a signed command is valid for after it is sent
+pub fn command_signature_ttl() -> i64 {
+    env_or("COMMAND_SIGNATURE_TTL", COMMAND_SIGNATURE_TTL)
+}
+
+/// If the deprecated GET /poll and GET /init routes are still served to robots
+pub fn legacy_robot_routes() -> bool {
+    env_flag("LEGACY_ROBOT_ROUTES", LEGACY_ROBOT_ROUTES)
+}
+
+/// How many times a webhook delivery is tried before it is marked as failed
+pub fn webhook_max_attempts() -> i64 {
+    env_or("WEBHOOK_MAX_ATTEMPTS", WEBHOOK_MAX_ATTEMPTS)
+}
+
+/// Seconds to wait before the first retry of a webhook delivery, doubling after each attempt
+pub fn webhook_retry_delay() -> i64 {
+    env_or("WEBHOOK_RETRY_DELAY", WEBHOOK_RETRY_DELAY)
+}
+
+/// Seconds to wait for a webhook to respond to a delivery
+pub fn webhook_timeout() -> i64 {
+    env_or("WEBHOOK_TIMEOUT", WEBHOOK_TIMEOUT)
+}
+
+/// The MQTT broker robots connect through, the MQTT transport is off if this is not set
+#[cfg(feature = "mqtt")]
+pub fn mqtt_host() -> Option<String> {
+    env::var("MQTT_HOST").ok()
+}
+
+/// The port of the MQTT broker
+#[cfg(feature = "mqtt")]
+pub fn mqtt_port() -> u16 {
+    env_or("MQTT_PORT", MQTT_PORT) as u16
+}
+
+/// The user name the backend logs in to the MQTT broker with
+#[cfg(feature = "mqtt")]
+pub fn mqtt_username() -> Option<String> {
+    env::var("MQTT_USERNAME").ok()
+}
+
+/// The password the backend logs in to the MQTT broker with
+#[cfg(feature = "mqtt")]
+pub fn mqtt_password() -> Option<String> {
+    env::var("MQTT_PASSWORD").ok()
+}
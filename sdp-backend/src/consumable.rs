@@ -0,0 +1,248 @@
+use crate::command::{CleaningMode, Command, Instruction};
+use crate::config;
+use crate::error::ApiError;
+use crate::event::{Event, RobotEvent};
+use chrono::{serde::ts_seconds_option, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use std::convert::TryFrom;
+
+// The parts of the robot that wear out and need replacing
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Component {
+    MainBrush,
+    SideBrush,
+    Filter,
+    MopPad,
+}
+
+// How worn a component is, the runtime and lifetime are in seconds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Consumable {
+    pub component: Component,
+    pub runtime: i64,
+    pub lifetime: i64,
+    pub remaining_percent: i64,
+    // If the component should be replaced soon
+    pub maintenance_due: bool,
+    // When the component was last replaced
+    #[serde(with = "ts_seconds_option")]
+    pub reset_at: Option<chrono::DateTime<Utc>>,
+}
+
+impl Component {
+    pub const ALL: [Component; 4] = [
+        Component::MainBrush,
+        Component::SideBrush,
+        Component::Filter,
+        Component::MopPad,
+    ];
+
+    // The number of seconds the component lasts before it should be replaced
+    pub fn lifetime(&self) -> i64 {
+        let hours = match self {
+            Component::MainBrush => config::main_brush_lifetime(),
+            Component::SideBrush => config::side_brush_lifetime(),
+            Component::Filter => config::filter_lifetime(),
+            Component::MopPad => config::mop_pad_lifetime(),
+        };
+
+        hours.saturating_mul(60 * 60)
+    }
+
+    // The components used when cleaning in the mode
+    pub fn used_by(mode: &Option<CleaningMode>) -> Vec<Component> {
+        match mode {
+            Some(CleaningMode::Mop) => vec![Component::MopPad],
+            Some(CleaningMode::VacuumAndMop) => Component::ALL.to_vec(),
+            _vacuum => vec![
+                Component::MainBrush,
+                Component::SideBrush,
+                Component::Filter,
+            ],
+        }
+    }
+}
+
+impl Consumable {
+    pub fn new(
+        component: Component,
+        runtime: i64,
+        reset_at: Option<chrono::DateTime<Utc>>,
+    ) -> Self {
+        let lifetime = component.lifetime();
+        let remaining_percent = if lifetime > 0 {
+            (100 - runtime.saturating_mul(100) / lifetime).max(0)
+        } else {
+            0
+        };
+
+        Self {
+            component,
+            runtime,
+            lifetime,
+            remaining_percent,
+            maintenance_due: remaining_percent <= config::maintenance_due_percent(),
+            reset_at,
+        }
+    }
+
+    // The wear of every component of the robot
+    pub async fn get_by_robot(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Vec<Self>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM Consumables C
+WHERE C.robot_serial_number = $1
+               "#,
+            robot_serial_number
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        // A row for a component that is not known is skipped
+        let mut used = Vec::new();
+        for c in results {
+            match Component::try_from(c.component.clone()) {
+                Ok(component) => used.push((component, c)),
+                Err(_) => log::error!("Skipped an unknown component {:?}", c.component),
+            }
+        }
+
+        // Components without a row have not been used yet
+        Ok(Component::ALL
+            .iter()
+            .map(
+                |component| match used.iter().find(|(used, _)| used == component) {
+                    Some((_, c)) => Self::new(*component, c.runtime, c.reset_at),
+                    None => Self::new(*component, 0, None),
+                },
+            )
+            .collect())
+    }
+
+    // Add the time spent on a completed task to the components it used. The duration
+    // from the session report is used if there is one, otherwise the time since the
+    // command was dispatched.
+    pub async fn record_task(
+        conn: &PgPool,
+        command: &Command,
+        duration: Option<i64>,
+    ) -> Result<(), ApiError> {
+        let mode = match &command.instruction {
+            Instruction::Task(_) => command.parameters.as_ref().and_then(|p| p.mode.clone()),
+            _ => return Ok(()),
+        };
+        let duration = duration.unwrap_or_else(|| command.time_since_dispatch());
+
+        for component in Component::used_by(&mode) {
+            Self::add_runtime(conn, &command.robot_serial_number, component, duration).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn add_runtime(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        component: Component,
+        duration: i64,
+    ) -> Result<(), ApiError> {
+        let updated = sqlx::query!(
+            r#"
+INSERT INTO Consumables (robot_serial_number, component, runtime)
+VALUES ($1, $2, $3)
+ON CONFLICT (robot_serial_number, component)
+DO UPDATE SET runtime = Consumables.runtime + $3
+RETURNING runtime, reset_at, notified
+               "#,
+            robot_serial_number,
+            component.to_string(),
+            duration
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        // Let the user know once when the component is due to be replaced
+        let consumable = Self::new(component, updated.runtime, updated.reset_at);
+        if consumable.maintenance_due && !updated.notified {
+            Event::record(
+                conn,
+                robot_serial_number,
+                Utc::now(),
+                &RobotEvent::MaintenanceDue(component),
+            )
+            .await?;
+
+            sqlx::query!(
+                r#"
+UPDATE Consumables
+SET notified = TRUE
+WHERE robot_serial_number = $1 AND component = $2
+               "#,
+                robot_serial_number,
+                component.to_string()
+            )
+            .execute(conn)
+            .await
+            .map_err(|_| ApiError::DatabaseConnFailed)?;
+        }
+
+        Ok(())
+    }
+
+    // Start counting the runtime again after the component has been replaced
+    pub async fn reset(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        component: Component,
+    ) -> Result<Self, ApiError> {
+        let reset_at = Utc::now();
+
+        sqlx::query!(
+            r#"
+INSERT INTO Consumables (robot_serial_number, component, runtime, notified, reset_at)
+VALUES ($1, $2, 0, FALSE, $3)
+ON CONFLICT (robot_serial_number, component)
+DO UPDATE SET runtime = 0, notified = FALSE, reset_at = $3
+               "#,
+            robot_serial_number,
+            component.to_string(),
+            reset_at
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(Self::new(component, 0, Some(reset_at)))
+    }
+}
+
+impl std::fmt::Display for Component {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Component::MainBrush => write!(f, "Component::MainBrush"),
+            Component::SideBrush => write!(f, "Component::SideBrush"),
+            Component::Filter => write!(f, "Component::Filter"),
+            Component::MopPad => write!(f, "Component::MopPad"),
+        }
+    }
+}
+
+impl TryFrom<String> for Component {
+    type Error = ApiError;
+
+    fn try_from(component: String) -> Result<Self, ApiError> {
+        match &component[..] {
+            "Component::MainBrush" => Ok(Component::MainBrush),
+            "Component::SideBrush" => Ok(Component::SideBrush),
+            "Component::Filter" => Ok(Component::Filter),
+            "Component::MopPad" => Ok(Component::MopPad),
+            _ => Err(ApiError::SerializationError),
+        }
+    }
+}
@@ -15,11 +15,25 @@ pub enum ApiError {
     SerializationError,
     AuthenticationFailed,
     RobotAlreadyAssigned,
+    InvalidTimeRange,
+    InvalidBatteryPolicy,
+    InvalidTaskParameters,
+    InvalidFirmware,
+    StorageFailed,
+    InvalidSessionReport,
+    InvalidMap,
+    InvalidZone,
+    UnsupportedFormat,
+    InvalidPayload,
+    InvalidSigningKey,
+    InvalidWebhook,
+    InvalidClockOffset,
+    InvalidTimeTolerance,
 }
 
 impl fmt::Display for ApiError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let error_json = serde_json::to_string(&self).unwrap_or(String::new());
+        let error_json = serde_json::to_string(&self).unwrap_or_default();
         write!(f, "{}", error_json)
     }
 }
@@ -45,6 +59,20 @@ impl From<ApiError> for HttpResponse {
             ApiError::SerializationError => HttpResponse::InternalServerError().json(error_json),
             ApiError::AuthenticationFailed => HttpResponse::Unauthorized().json(error_json),
             ApiError::RobotAlreadyAssigned => HttpResponse::BadRequest().json(error_json),
+            ApiError::InvalidTimeRange => HttpResponse::BadRequest().json(error_json),
+            ApiError::InvalidBatteryPolicy => HttpResponse::BadRequest().json(error_json),
+            ApiError::InvalidTaskParameters => HttpResponse::BadRequest().json(error_json),
+            ApiError::InvalidFirmware => HttpResponse::BadRequest().json(error_json),
+            ApiError::StorageFailed => HttpResponse::InternalServerError().json(error_json),
+            ApiError::InvalidSessionReport => HttpResponse::BadRequest().json(error_json),
+            ApiError::InvalidMap => HttpResponse::BadRequest().json(error_json),
+            ApiError::InvalidZone => HttpResponse::BadRequest().json(error_json),
+            ApiError::UnsupportedFormat => HttpResponse::UnsupportedMediaType().json(error_json),
+            ApiError::InvalidPayload => HttpResponse::BadRequest().json(error_json),
+            ApiError::InvalidSigningKey => HttpResponse::InternalServerError().json(error_json),
+            ApiError::InvalidWebhook => HttpResponse::BadRequest().json(error_json),
+            ApiError::InvalidClockOffset => HttpResponse::BadRequest().json(error_json),
+            ApiError::InvalidTimeTolerance => HttpResponse::BadRequest().json(error_json),
         }
     }
 }
@@ -0,0 +1,377 @@
+use crate::command::{Instruction, Status};
+use crate::config;
+use crate::consumable::Component;
+use crate::error::ApiError;
+use crate::robot::RobotState;
+use crate::webhook::{DeliveryStatus, WebhookDelivery};
+use chrono::{serde::ts_seconds, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use std::convert::TryFrom;
+
+// The number of events returned when listing the history for a robot
+const EVENT_HISTORY_LIMIT: i64 = 100;
+
+// The Postgres channel the id of each new event is sent on
+pub const EVENT_CHANNEL: &str = "robot_events";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Event {
+    pub event_id: i64,
+    pub robot_serial_number: String,
+    #[serde(with = "ts_seconds")]
+    pub time: chrono::DateTime<Utc>,
+    pub event: RobotEvent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RobotEvent {
+    // The robot has started polling the server
+    Online,
+    // The robot has not polled the server within the offline threshold
+    Offline,
+    // The robot reported a new fault, with the id of the fault
+    Fault(i64),
+    // The command was paused because the robot is blocked by an obstacle
+    ObstaclePaused(i64),
+    // The obstacle was cleared and the command has carried on
+    ObstacleCleared(i64),
+    // The command was cancelled as the obstacle was not cleared in time
+    ObstacleTimeout(i64),
+    // The component is worn and should be replaced
+    MaintenanceDue(Component),
+    // The status of a command has changed
+    CommandStatus(CommandStatusChange),
+    // The robot reported a new battery level
+    Battery(BatteryChange),
+    // The robot has left or returned to the dock, or started or stopped charging
+    StateChanged(RobotState),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CommandStatusChange {
+    pub command_id: i64,
+    pub status: Status,
+    // What the command was for, e.g. a safety abort
+    #[serde(default)]
+    pub instruction: Option<Instruction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatteryChange {
+    pub battery_level: i64,
+    pub charging: bool,
+}
+
+impl Event {
+    pub async fn record(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        time: chrono::DateTime<Utc>,
+        event: &RobotEvent,
+    ) -> Result<Self, ApiError> {
+        let recorded = sqlx::query!(
+            r#"
+INSERT INTO Events (robot_serial_number, time, event)
+VALUES ($1, $2, $3)
+RETURNING event_id
+               "#,
+            robot_serial_number,
+            time,
+            event.to_string()
+        )
+        .fetch_one(conn)
+        .await
+        .map(|e| Self {
+            event_id: e.event_id,
+            robot_serial_number: robot_serial_number.to_string(),
+            time,
+            event: event.clone(),
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        // Let anyone listening know there is a new event, the listener reads the event back
+        sqlx::query!(
+            r#"
+SELECT pg_notify($1, $2)::TEXT AS notified
+               "#,
+            EVENT_CHANNEL,
+            recorded.event_id.to_string()
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        // Queue the event for any webhooks that want it
+        WebhookDelivery::enqueue(conn, &recorded).await?;
+
+        Ok(recorded)
+    }
+
+    pub async fn get_by_id(conn: &PgPool, event_id: i64) -> Result<Self, ApiError> {
+        let e = sqlx::query!(
+            r#"
+SELECT * FROM Events E
+WHERE E.event_id = $1
+               "#,
+            event_id
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(Self {
+            event_id: e.event_id,
+            robot_serial_number: e.robot_serial_number,
+            time: e.time,
+            event: RobotEvent::try_from(e.event)?,
+        })
+    }
+
+    // Get the most recent events for the robot, newest first. Battery changes are left
+    // out as they come with nearly every poll and would push out the other events, the
+    // current battery level is in the robot status.
+    pub async fn get_by_robot(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Vec<Self>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM Events E
+WHERE E.robot_serial_number = $1 AND
+      E.event NOT LIKE 'RobotEvent::Battery(%'
+ORDER BY E.time DESC, E.event_id DESC
+LIMIT $2
+               "#,
+            robot_serial_number,
+            EVENT_HISTORY_LIMIT
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        // An event that is not known is skipped
+        let mut events = Vec::new();
+        for e in results {
+            match RobotEvent::try_from(e.event) {
+                Ok(event) => events.push(Self {
+                    event_id: e.event_id,
+                    robot_serial_number: e.robot_serial_number,
+                    time: e.time,
+                    event,
+                }),
+                Err(_) => log::error!("Skipped the unknown event {}", e.event_id),
+            }
+        }
+
+        Ok(events)
+    }
+
+    // Get the events for the robot after the given event, oldest first. Only the events
+    // inside the replay window are kept for clients that resume a stream.
+    pub async fn get_since(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        event_id: i64,
+    ) -> Result<Vec<Self>, ApiError> {
+        let window_start = Utc::now() - chrono::Duration::seconds(config::event_replay_window());
+
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM Events E
+WHERE E.robot_serial_number = $1 AND
+      E.event_id > $2 AND
+      E.time >= $3
+ORDER BY E.event_id
+               "#,
+            robot_serial_number,
+            event_id,
+            window_start
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        // An event that is not known is skipped
+        let mut events = Vec::new();
+        for e in results {
+            match RobotEvent::try_from(e.event) {
+                Ok(event) => events.push(Self {
+                    event_id: e.event_id,
+                    robot_serial_number: e.robot_serial_number,
+                    time: e.time,
+                    event,
+                }),
+                Err(_) => log::error!("Skipped the unknown event {}", e.event_id),
+            }
+        }
+
+        Ok(events)
+    }
+
+    // Remove the events from before the replay window, they are no longer sent to clients
+    // resuming a stream. An event still waiting to be sent to a webhook is kept until it
+    // has been, and the last online or offline event of each robot is kept as it is needed
+    // to notice the robot going offline.
+    pub async fn prune(conn: &PgPool) -> Result<(), ApiError> {
+        let window_start = Utc::now() - chrono::Duration::seconds(config::event_replay_window());
+
+        sqlx::query!(
+            r#"
+WITH expired AS (
+     SELECT E.event_id FROM Events E
+     WHERE E.time < $1 AND
+           NOT EXISTS (SELECT 1 FROM WebhookDeliveries D
+                       WHERE D.event_id = E.event_id AND D.status = $2) AND
+           E.event_id NOT IN (SELECT DISTINCT ON (P.robot_serial_number) P.event_id FROM Events P
+                              WHERE P.event = 'RobotEvent::Online' OR P.event = 'RobotEvent::Offline'
+                              ORDER BY P.robot_serial_number, P.time DESC, P.event_id DESC)),
+     deliveries AS (DELETE FROM WebhookDeliveries WHERE event_id IN (SELECT event_id FROM expired))
+DELETE FROM Events
+WHERE event_id IN (SELECT event_id FROM expired)
+               "#,
+            window_start,
+            DeliveryStatus::Pending.to_string()
+        )
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // Get the last online or offline event recorded for the robot
+    pub async fn last_presence(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Option<RobotEvent>, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT E.event FROM Events E
+WHERE E.robot_serial_number = $1 AND
+     (E.event = 'RobotEvent::Online' OR E.event = 'RobotEvent::Offline')
+ORDER BY E.time DESC, E.event_id DESC
+LIMIT 1
+               "#,
+            robot_serial_number
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?
+        .map(|e| RobotEvent::try_from(e.event))
+        .transpose()
+    }
+}
+
+impl std::fmt::Display for RobotEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RobotEvent::Online => write!(f, "RobotEvent::Online"),
+            RobotEvent::Offline => write!(f, "RobotEvent::Offline"),
+            RobotEvent::Fault(fault_id) => write!(f, "RobotEvent::Fault({})", fault_id),
+            RobotEvent::ObstaclePaused(command_id) => {
+                write!(f, "RobotEvent::ObstaclePaused({})", command_id)
+            }
+            RobotEvent::ObstacleCleared(command_id) => {
+                write!(f, "RobotEvent::ObstacleCleared({})", command_id)
+            }
+            RobotEvent::ObstacleTimeout(command_id) => {
+                write!(f, "RobotEvent::ObstacleTimeout({})", command_id)
+            }
+            RobotEvent::MaintenanceDue(component) => {
+                write!(f, "RobotEvent::MaintenanceDue({})", component)
+            }
+            RobotEvent::CommandStatus(change) => write!(
+                f,
+                "RobotEvent::CommandStatus({})",
+                serde_json::to_string(change).unwrap_or_default()
+            ),
+            RobotEvent::Battery(change) => write!(
+                f,
+                "RobotEvent::Battery({})",
+                serde_json::to_string(change).unwrap_or_default()
+            ),
+            RobotEvent::StateChanged(state) => write!(f, "RobotEvent::StateChanged({})", state),
+        }
+    }
+}
+
+impl TryFrom<String> for RobotEvent {
+    type Error = ApiError;
+
+    fn try_from(event: String) -> Result<Self, ApiError> {
+        let event = match &event[..] {
+            "RobotEvent::Online" => Some(RobotEvent::Online),
+            "RobotEvent::Offline" => Some(RobotEvent::Offline),
+            e if e.starts_with("RobotEvent::MaintenanceDue(") && e.ends_with(')') => {
+                Component::try_from(e["RobotEvent::MaintenanceDue(".len()..e.len() - 1].to_string())
+                    .map(RobotEvent::MaintenanceDue)
+                    .ok()
+            }
+            e if e.starts_with("RobotEvent::CommandStatus(") && e.ends_with(')') => {
+                serde_json::from_str(&e["RobotEvent::CommandStatus(".len()..e.len() - 1])
+                    .map(RobotEvent::CommandStatus)
+                    .ok()
+            }
+            e if e.starts_with("RobotEvent::Battery(") && e.ends_with(')') => {
+                serde_json::from_str(&e["RobotEvent::Battery(".len()..e.len() - 1])
+                    .map(RobotEvent::Battery)
+                    .ok()
+            }
+            e if e.starts_with("RobotEvent::StateChanged(") && e.ends_with(')') => {
+                Some(RobotEvent::StateChanged(
+                    e["RobotEvent::StateChanged(".len()..e.len() - 1]
+                        .to_string()
+                        .into(),
+                ))
+            }
+            e => Self::parse_with_id(e),
+        };
+
+        event.ok_or(ApiError::SerializationError)
+    }
+}
+
+impl RobotEvent {
+    // The names of each kind of event, used to choose the events a webhook is sent
+    pub const KINDS: [&'static str; 10] = [
+        "Online",
+        "Offline",
+        "Fault",
+        "ObstaclePaused",
+        "ObstacleCleared",
+        "ObstacleTimeout",
+        "MaintenanceDue",
+        "CommandStatus",
+        "Battery",
+        "StateChanged",
+    ];
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RobotEvent::Online => "Online",
+            RobotEvent::Offline => "Offline",
+            RobotEvent::Fault(_) => "Fault",
+            RobotEvent::ObstaclePaused(_) => "ObstaclePaused",
+            RobotEvent::ObstacleCleared(_) => "ObstacleCleared",
+            RobotEvent::ObstacleTimeout(_) => "ObstacleTimeout",
+            RobotEvent::MaintenanceDue(_) => "MaintenanceDue",
+            RobotEvent::CommandStatus(_) => "CommandStatus",
+            RobotEvent::Battery(_) => "Battery",
+            RobotEvent::StateChanged(_) => "StateChanged",
+        }
+    }
+
+    // Read an event stored with an id, e.g. "RobotEvent::Fault(3)"
+    fn parse_with_id(event: &str) -> Option<Self> {
+        let start = event.find('(')?;
+        let id = event[start + 1..].strip_suffix(')')?.parse().ok()?;
+
+        match &event[..start] {
+            "RobotEvent::Fault" => Some(RobotEvent::Fault(id)),
+            "RobotEvent::ObstaclePaused" => Some(RobotEvent::ObstaclePaused(id)),
+            "RobotEvent::ObstacleCleared" => Some(RobotEvent::ObstacleCleared(id)),
+            "RobotEvent::ObstacleTimeout" => Some(RobotEvent::ObstacleTimeout(id)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::error::ApiError;
+use crate::event::{Event, RobotEvent};
+use chrono::{
+    serde::{ts_seconds, ts_seconds_option},
+    Utc,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum FaultSeverity {
+    Info,
+    Warning,
+    Error,
+    // The robot can not carry on until the fault is fixed
+    Critical,
+}
+
+// A fault as reported by the robot when it polls
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FaultReport {
+    // A code identifying the fault, e.g. "BrushJammed" or "WheelStuck"
+    pub code: String,
+    pub severity: FaultSeverity,
+    #[serde(default)]
+    pub message: String,
+    // When the fault happened on the robot
+    #[serde(with = "ts_seconds")]
+    pub time: chrono::DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub fault_id: i64,
+    pub robot_serial_number: String,
+    // The command the robot was running when the fault happened
+    pub command_id: Option<i64>,
+    pub code: String,
+    pub severity: FaultSeverity,
+    pub message: String,
+    #[serde(with = "ts_seconds")]
+    pub time: chrono::DateTime<Utc>,
+    // When the user acknowledged the fault, None while the fault is active
+    #[serde(with = "ts_seconds_option")]
+    pub acknowledged_at: Option<chrono::DateTime<Utc>>,
+    // When the robot stopped reporting the fault, None while it is still reported
+    #[serde(default, with = "ts_seconds_option")]
+    pub cleared_at: Option<chrono::DateTime<Utc>>,
+}
+
+impl Fault {
+    // Store a fault reported by the robot and notify the user. Robots keep reporting
+    // a fault until it is fixed, so while the robot is still reporting a fault with
+    // the same code that fault is returned instead of storing it again, even if the
+    // user has acknowledged it.
+    pub async fn report(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        command_id: Option<i64>,
+        report: &FaultReport,
+    ) -> Result<Self, ApiError> {
+        if let Some(f) = Self::get_reported(conn, robot_serial_number, &report.code).await? {
+            return Ok(f);
+        }
+
+        let fault = sqlx::query!(
+            r#"
+INSERT INTO Faults (robot_serial_number, command_id, code, severity, message, time)
+VALUES ($1, $2, $3, $4, $5, $6)
+RETURNING fault_id
+               "#,
+            robot_serial_number,
+            command_id,
+            report.code,
+            report.severity.to_string(),
+            report.message,
+            report.time
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Event::record(
+            conn,
+            robot_serial_number,
+            Utc::now(),
+            &RobotEvent::Fault(fault.fault_id),
+        )
+        .await?;
+
+        Self::get_by_id(conn, fault.fault_id).await
+    }
+
+    pub async fn get_by_id(conn: &PgPool, fault_id: i64) -> Result<Self, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT * FROM Faults F
+WHERE F.fault_id = $1
+               "#,
+            fault_id
+        )
+        .fetch_one(conn)
+        .await
+        .map(|f| Self {
+            fault_id: f.fault_id,
+            robot_serial_number: f.robot_serial_number,
+            command_id: f.command_id,
+            code: f.code,
+            severity: f.severity.into(),
+            message: f.message,
+            time: f.time,
+            acknowledged_at: f.acknowledged_at,
+            cleared_at: f.cleared_at,
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // Mark the faults the robot is no longer reporting as cleared, so the fault
+    // is stored again if it comes back
+    pub async fn clear_missing(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        reports: &[FaultReport],
+    ) -> Result<(), ApiError> {
+        let codes: Vec<String> = reports.iter().map(|r| r.code.clone()).collect();
+
+        sqlx::query!(
+            r#"
+UPDATE Faults
+SET cleared_at = $2
+WHERE robot_serial_number = $1 AND
+      cleared_at IS NULL AND
+      NOT (code = ANY($3))
+               "#,
+            robot_serial_number,
+            Utc::now(),
+            &codes[..]
+        )
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // The latest fault with the code the robot is still reporting, acknowledged or not
+    async fn get_reported(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        code: &str,
+    ) -> Result<Option<Self>, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT * FROM Faults F
+WHERE F.robot_serial_number = $1 AND
+      F.code = $2 AND
+      F.cleared_at IS NULL
+ORDER BY F.time DESC, F.fault_id DESC
+LIMIT 1
+               "#,
+            robot_serial_number,
+            code
+        )
+        .fetch_optional(conn)
+        .await
+        .map(|f| {
+            f.map(|f| Self {
+                fault_id: f.fault_id,
+                robot_serial_number: f.robot_serial_number,
+                command_id: f.command_id,
+                code: f.code,
+                severity: f.severity.into(),
+                message: f.message,
+                time: f.time,
+                acknowledged_at: f.acknowledged_at,
+                cleared_at: f.cleared_at,
+            })
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // The faults for the robot that have not been acknowledged, newest first
+    pub async fn get_active(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Vec<Self>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM Faults F
+WHERE F.robot_serial_number = $1 AND F.acknowledged_at IS NULL
+ORDER BY F.time DESC, F.fault_id DESC
+               "#,
+            robot_serial_number
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(results
+            .into_iter()
+            .map(|f| Self {
+                fault_id: f.fault_id,
+                robot_serial_number: f.robot_serial_number,
+                command_id: f.command_id,
+                code: f.code,
+                severity: f.severity.into(),
+                message: f.message,
+                time: f.time,
+                acknowledged_at: f.acknowledged_at,
+                cleared_at: f.cleared_at,
+            })
+            .collect())
+    }
+
+    pub async fn acknowledge(&self, conn: &PgPool) -> Result<Self, ApiError> {
+        sqlx::query!(
+            r#"
+UPDATE Faults
+SET acknowledged_at = COALESCE(acknowledged_at, $2)
+WHERE fault_id = $1
+               "#,
+            self.fault_id,
+            Utc::now()
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get_by_id(conn, self.fault_id).await
+    }
+}
+
+impl std::fmt::Display for FaultSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FaultSeverity::Info => write!(f, "FaultSeverity::Info"),
+            FaultSeverity::Warning => write!(f, "FaultSeverity::Warning"),
+            FaultSeverity::Error => write!(f, "FaultSeverity::Error"),
+            FaultSeverity::Critical => write!(f, "FaultSeverity::Critical"),
+        }
+    }
+}
+
+impl From<String> for FaultSeverity {
+    fn from(severity: String) -> Self {
+        match &severity[..] {
+            "FaultSeverity::Info" => FaultSeverity::Info,
+            "FaultSeverity::Warning" => FaultSeverity::Warning,
+            "FaultSeverity::Error" => FaultSeverity::Error,
+            _ => FaultSeverity::Critical,
+        }
+    }
+}
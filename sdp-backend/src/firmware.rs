@@ -0,0 +1,240 @@
+use crate::error::ApiError;
+use crate::robot::Robot;
+use crate::storage::Storage;
+use chrono::{serde::ts_seconds, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgPool;
+
+mod update;
+
+pub use update::{FirmwareStatus, FirmwareUpdate};
+
+// A firmware version that can be installed on robots of the target models
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FirmwareRelease {
+    pub release_id: i64,
+    pub version: String,
+    // The hex encoded SHA-256 of the artifact
+    pub checksum: String,
+    pub target_models: Vec<String>,
+    // The percentage of the target robots the release is offered to
+    pub rollout_percentage: i64,
+    // The storage key for the artifact
+    #[serde(skip)]
+    pub artifact_key: String,
+    #[serde(with = "ts_seconds")]
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+// The release details sent to the robot with the update instruction,
+// the artifact is downloaded from /firmware/{release_id}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FirmwareOffer {
+    pub release_id: i64,
+    pub version: String,
+    pub checksum: String,
+}
+
+impl FirmwareRelease {
+    // Store the artifact and add the release, the checksum given must match the artifact
+    pub async fn new(
+        conn: &PgPool,
+        storage: &impl Storage,
+        version: &str,
+        checksum: &str,
+        target_models: &[String],
+        rollout_percentage: i64,
+        artifact: &[u8],
+    ) -> Result<Self, ApiError> {
+        // The version is used as the file name so only allow simple names
+        let valid_version = !version.is_empty()
+            && version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+        let valid_checksum = Self::checksum_of(artifact).eq_ignore_ascii_case(checksum);
+        let valid_rollout = (0..=100).contains(&rollout_percentage);
+
+        if !valid_version || !valid_checksum || !valid_rollout || target_models.is_empty() {
+            return Err(ApiError::InvalidFirmware);
+        }
+
+        // Each version can only be released once, so the stored artifact is never replaced
+        let existing = sqlx::query!(
+            r#"
+SELECT F.release_id FROM FirmwareReleases F
+WHERE F.version = $1
+               "#,
+            version
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+        if existing.is_some() {
+            return Err(ApiError::InvalidFirmware);
+        }
+
+        let artifact_key = format!("firmware/{}.bin", version);
+        storage.put(&artifact_key, artifact).await?;
+
+        let release = sqlx::query!(
+            r#"
+INSERT INTO FirmwareReleases (version, checksum, target_models, rollout_percentage, artifact_key, created_at)
+VALUES ($1, $2, $3, $4, $5, $6)
+RETURNING release_id
+               "#,
+            version,
+            checksum.to_lowercase(),
+            target_models,
+            rollout_percentage,
+            artifact_key,
+            Utc::now()
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get(conn, release.release_id).await
+    }
+
+    pub async fn get(conn: &PgPool, release_id: i64) -> Result<Self, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT * FROM FirmwareReleases F
+WHERE F.release_id = $1
+               "#,
+            release_id
+        )
+        .fetch_one(conn)
+        .await
+        .map(|r| Self {
+            release_id: r.release_id,
+            version: r.version,
+            checksum: r.checksum,
+            target_models: r.target_models,
+            rollout_percentage: r.rollout_percentage,
+            artifact_key: r.artifact_key,
+            created_at: r.created_at,
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    pub async fn get_all(conn: &PgPool) -> Result<Vec<Self>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM FirmwareReleases F
+ORDER BY F.release_id
+               "#,
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| Self {
+                release_id: r.release_id,
+                version: r.version,
+                checksum: r.checksum,
+                target_models: r.target_models,
+                rollout_percentage: r.rollout_percentage,
+                artifact_key: r.artifact_key,
+                created_at: r.created_at,
+            })
+            .collect())
+    }
+
+    // Change how many of the target robots are offered the release
+    pub async fn set_rollout(
+        conn: &PgPool,
+        release_id: i64,
+        rollout_percentage: i64,
+    ) -> Result<Self, ApiError> {
+        if !(0..=100).contains(&rollout_percentage) {
+            return Err(ApiError::InvalidFirmware);
+        }
+
+        sqlx::query!(
+            r#"
+UPDATE FirmwareReleases
+SET rollout_percentage = $2
+WHERE release_id = $1
+               "#,
+            release_id,
+            rollout_percentage
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get(conn, release_id).await
+    }
+
+    // The newest release for the robots model, if the robot is not already running it,
+    // is part of the rollout and has not already failed to install it
+    pub async fn available(conn: &PgPool, robot: &Robot) -> Result<Option<Self>, ApiError> {
+        // Only robots that report their model and firmware can be updated
+        let (model, firmware_version) = match (&robot.model, &robot.firmware_version) {
+            (Some(m), Some(v)) => (m, v),
+            _unknown => return Ok(None),
+        };
+
+        let latest = sqlx::query!(
+            r#"
+SELECT F.release_id FROM FirmwareReleases F
+WHERE $1 = ANY(F.target_models)
+ORDER BY F.release_id DESC
+LIMIT 1
+               "#,
+            model
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        let release = match latest {
+            Some(r) => Self::get(conn, r.release_id).await?,
+            None => return Ok(None),
+        };
+        if &release.version == firmware_version || !release.in_rollout(&robot.robot_serial_number) {
+            return Ok(None);
+        }
+
+        match FirmwareUpdate::get(conn, &robot.robot_serial_number, release.release_id).await? {
+            Some(u) if u.status == FirmwareStatus::Failed => Ok(None),
+            _ => Ok(Some(release)),
+        }
+    }
+
+    // Robots are placed in one of 100 buckets using a hash of their serial number,
+    // so increasing the percentage keeps offering the release to the same robots
+    pub fn in_rollout(&self, robot_serial_number: &str) -> bool {
+        let hash = Sha256::digest(robot_serial_number.as_bytes());
+        let bucket = hash
+            .iter()
+            .take(8)
+            .fold(0u64, |acc, b| (acc << 8) | u64::from(*b))
+            % 100;
+
+        (bucket as i64) < self.rollout_percentage
+    }
+
+    pub fn offer(&self) -> FirmwareOffer {
+        FirmwareOffer {
+            release_id: self.release_id,
+            version: self.version.clone(),
+            checksum: self.checksum.clone(),
+        }
+    }
+
+    pub async fn artifact(&self, storage: &impl Storage) -> Result<Vec<u8>, ApiError> {
+        storage.get(&self.artifact_key).await
+    }
+
+    pub fn checksum_of(artifact: &[u8]) -> String {
+        Sha256::digest(artifact)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
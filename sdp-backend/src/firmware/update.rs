@@ -0,0 +1,165 @@
+use crate::error::ApiError;
+use crate::firmware::FirmwareRelease;
+use crate::robot::Robot;
+use chrono::{serde::ts_seconds, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum FirmwareStatus {
+    // The update has been sent to the robot
+    Offered,
+    Downloading,
+    Installing,
+    Succeeded,
+    Failed,
+}
+
+// The progress of a release being installed on a robot
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FirmwareUpdate {
+    pub robot_serial_number: String,
+    pub release_id: i64,
+    pub status: FirmwareStatus,
+    #[serde(with = "ts_seconds")]
+    pub updated_at: chrono::DateTime<Utc>,
+}
+
+impl FirmwareUpdate {
+    pub async fn set(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        release_id: i64,
+        status: &FirmwareStatus,
+    ) -> Result<Self, ApiError> {
+        let updated_at = Utc::now();
+
+        sqlx::query!(
+            r#"
+INSERT INTO FirmwareUpdates (robot_serial_number, release_id, status, updated_at)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (robot_serial_number, release_id)
+DO UPDATE SET status = $3, updated_at = $4
+               "#,
+            robot_serial_number,
+            release_id,
+            status.to_string(),
+            updated_at
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(Self {
+            robot_serial_number: robot_serial_number.to_string(),
+            release_id,
+            status: status.clone(),
+            updated_at,
+        })
+    }
+
+    pub async fn get(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        release_id: i64,
+    ) -> Result<Option<Self>, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT * FROM FirmwareUpdates U
+WHERE U.robot_serial_number = $1 AND U.release_id = $2
+               "#,
+            robot_serial_number,
+            release_id
+        )
+        .fetch_optional(conn)
+        .await
+        .map(|r| {
+            r.map(|r| Self {
+                robot_serial_number: r.robot_serial_number,
+                release_id: r.release_id,
+                status: r.status.into(),
+                updated_at: r.updated_at,
+            })
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    pub async fn get_by_robot(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Vec<Self>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM FirmwareUpdates U
+WHERE U.robot_serial_number = $1
+ORDER BY U.updated_at DESC
+               "#,
+            robot_serial_number
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| Self {
+                robot_serial_number: r.robot_serial_number,
+                release_id: r.release_id,
+                status: r.status.into(),
+                updated_at: r.updated_at,
+            })
+            .collect())
+    }
+
+    // The robot restarts after installing an update, so when it initialises check the
+    // firmware it reports against the updates it was part way through
+    pub async fn check_installed(conn: &PgPool, robot: &Robot) -> Result<(), ApiError> {
+        let updates = Self::get_by_robot(conn, &robot.robot_serial_number).await?;
+
+        for update in updates.into_iter().filter(|u| !u.status.is_finished()) {
+            let release = FirmwareRelease::get(conn, update.release_id).await?;
+
+            let status = if robot.firmware_version.as_ref() == Some(&release.version) {
+                FirmwareStatus::Succeeded
+            } else if update.status == FirmwareStatus::Installing {
+                FirmwareStatus::Failed
+            } else {
+                continue;
+            };
+
+            Self::set(conn, &robot.robot_serial_number, update.release_id, &status).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FirmwareStatus {
+    pub fn is_finished(&self) -> bool {
+        self == &Self::Succeeded || self == &Self::Failed
+    }
+}
+
+impl std::fmt::Display for FirmwareStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirmwareStatus::Offered => write!(f, "FirmwareStatus::Offered"),
+            FirmwareStatus::Downloading => write!(f, "FirmwareStatus::Downloading"),
+            FirmwareStatus::Installing => write!(f, "FirmwareStatus::Installing"),
+            FirmwareStatus::Succeeded => write!(f, "FirmwareStatus::Succeeded"),
+            FirmwareStatus::Failed => write!(f, "FirmwareStatus::Failed"),
+        }
+    }
+}
+
+impl From<String> for FirmwareStatus {
+    fn from(status: String) -> Self {
+        match &status[..] {
+            "FirmwareStatus::Offered" => FirmwareStatus::Offered,
+            "FirmwareStatus::Downloading" => FirmwareStatus::Downloading,
+            "FirmwareStatus::Installing" => FirmwareStatus::Installing,
+            "FirmwareStatus::Succeeded" => FirmwareStatus::Succeeded,
+            _ => FirmwareStatus::Failed,
+        }
+    }
+}
@@ -1,18 +1,36 @@
-use actix_web::{middleware::Logger, App, HttpServer};
+use actix::Actor;
+use actix_web::{guard, middleware::Logger, web, App, HttpServer};
 use std::env;
 
 mod api;
 mod auth;
+mod battery;
+mod clock;
 mod command;
+mod config;
+mod consumable;
 mod error;
+mod event;
+mod fault;
+mod firmware;
+mod map;
+#[cfg(feature = "mqtt")]
+mod mqtt;
 mod poll;
+mod report;
 mod robot;
+mod signing;
+mod storage;
+mod stream;
 mod test;
 mod user;
+mod webhook;
+mod wire;
+mod zone;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    std::env::set_var("RUST_LOG", "actix_web=debug");
+    std::env::set_var("RUST_LOG", "actix_web=debug,sdp_backend=info");
     env_logger::init();
 
     // Default port for the webserver is 8080, this can be overwritten
@@ -26,24 +44,99 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("to get database pool");
 
+    // The key the commands sent to the robots are signed with
+    let signer = signing::CommandSigner::load(&config::signing_key_file())
+        .expect("to load the command signing key");
+
+    // Pass the events recorded to the open WebSockets and event streams
+    let hub = stream::EventHub::default().start();
+    actix_rt::spawn(stream::listen(database_pool.clone(), hub.clone()));
+
+    // Send the events to the webhooks users have registered
+    actix_rt::spawn(webhook::deliver(database_pool.clone()));
+
+    // Robots can also connect through an MQTT broker
+    #[cfg(feature = "mqtt")]
+    if let Some(host) = config::mqtt_host() {
+        mqtt::start(
+            database_pool.clone(),
+            signer.clone(),
+            host,
+            config::mqtt_port(),
+        );
+    }
+
     HttpServer::new(move || {
         App::new()
             .wrap(actix_cors::Cors::default().allow_any_origin())
             .wrap(Logger::default())
             .data(database_pool.clone())
+            .data(hub.clone())
+            .data(storage::LocalStorage::new(&config::storage_dir()))
+            .data(signer.clone())
             // User Endpoints
             .service(api::user::create_user)
             .service(api::user::user_status)
+            .service(api::user::user_events)
+            .service(api::socket::event_socket)
+            .service(api::stream::event_stream)
+            .service(api::user::user_patterns)
+            .service(api::battery::battery_history)
+            .service(api::report::session_stats)
+            .service(api::battery::get_battery_policy)
+            .service(api::battery::set_battery_policy)
             // Command Endpoints
             .service(api::command::create_command)
             .service(api::command::get_command)
             .service(api::command::cancel_command)
+            .service(api::firmware::firmware_updates)
+            .service(api::fault::active_faults)
+            .service(api::fault::acknowledge_fault)
+            .service(api::map::get_maps)
+            .service(api::map::download_map)
+            .service(api::zone::get_zones)
+            .service(api::zone::create_zone)
+            .service(api::zone::delete_zone)
+            .service(api::webhook::get_webhooks)
+            .service(api::webhook::create_webhook)
+            .service(api::webhook::delete_webhook)
+            .service(api::webhook::webhook_deliveries)
+            .service(api::consumable::get_consumables)
+            .service(api::consumable::reset_consumable)
             .service(api::auth::auth)
+            // Time Sync Endpoint
+            .service(api::time::time_sync)
             // Robot Endpoints
             .service(api::poll::robot_poll)
             .service(api::poll::robot_init)
+            .service(api::poll::signing_key)
+            .configure(|cfg| {
+                if config::legacy_robot_routes() {
+                    cfg.service(api::poll::legacy_robot_poll)
+                        .service(api::poll::legacy_robot_init);
+                }
+            })
+            .service(api::firmware::download_firmware)
+            // Maps are uploaded as the request body, so this route takes larger bodies
+            .service(
+                web::resource("/map")
+                    .guard(guard::Post())
+                    .app_data(web::PayloadConfig::new(config::upload_max_size()))
+                    .to(api::map::upload_map),
+            )
             // Admin Endpoints
             .service(api::admin::create_robot)
+            .service(api::admin::set_time_tolerance)
+            .service(api::admin::set_battery_policy)
+            .service(api::admin::set_model_patterns)
+            .service(
+                web::resource("/admin/firmware")
+                    .guard(guard::Post())
+                    .app_data(web::PayloadConfig::new(config::upload_max_size()))
+                    .to(api::admin::upload_firmware),
+            )
+            .service(api::admin::get_firmware_releases)
+            .service(api::admin::set_firmware_rollout)
             // Static Files Endpoint
             .service(actix_files::Files::new("/static", "/static").show_files_listing())
     })
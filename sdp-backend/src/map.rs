@@ -0,0 +1,199 @@
+use crate::error::ApiError;
+use crate::robot::Robot;
+use crate::storage::Storage;
+use chrono::{serde::ts_seconds, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+// Where the map sits in the robots coordinate frame, in meters
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MapMetadata {
+    // The size of a pixel or grid cell
+    pub resolution: f64,
+    // The position of the bottom left of the map
+    #[serde(default)]
+    pub origin_x: f64,
+    #[serde(default)]
+    pub origin_y: f64,
+}
+
+// A floor map uploaded by the robot, each upload is kept as a new version
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Map {
+    pub map_id: i64,
+    pub robot_serial_number: String,
+    pub version: i64,
+    // The format of the map data, e.g. "image/png"
+    pub content_type: String,
+    #[serde(flatten)]
+    pub metadata: MapMetadata,
+    // The size of the map data, in bytes
+    pub size: i64,
+    #[serde(skip)]
+    pub storage_key: String,
+    #[serde(with = "ts_seconds")]
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+impl MapMetadata {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let valid_resolution = self.resolution.is_finite() && self.resolution > 0.0;
+        let valid_origin = self.origin_x.is_finite() && self.origin_y.is_finite();
+
+        if valid_resolution && valid_origin {
+            Ok(())
+        } else {
+            Err(ApiError::InvalidMap)
+        }
+    }
+}
+
+impl Map {
+    // Store the map data as the next version of the robots map
+    pub async fn new(
+        conn: &PgPool,
+        storage: &impl Storage,
+        robot_serial_number: &str,
+        content_type: &str,
+        metadata: &MapMetadata,
+        data: &[u8],
+    ) -> Result<Self, ApiError> {
+        metadata.validate()?;
+        if data.is_empty() {
+            return Err(ApiError::InvalidMap);
+        }
+
+        // Make sure the robot exists before storing anything for it
+        Robot::get_by_serial(conn, robot_serial_number).await?;
+
+        // The row is added before the map data is stored. The robot is locked until the
+        // upload is done, so two uploads at once can not be given the same version.
+        let mut tx = conn
+            .begin()
+            .await
+            .map_err(|_| ApiError::DatabaseConnFailed)?;
+        sqlx::query!(
+            r#"
+SELECT R.robot_serial_number FROM Robot R
+WHERE R.robot_serial_number = $1
+FOR UPDATE
+               "#,
+            robot_serial_number
+        )
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        let version = sqlx::query!(
+            r#"
+SELECT COALESCE(MAX(M.version), 0) + 1 AS "version!" FROM Maps M
+WHERE M.robot_serial_number = $1
+               "#,
+            robot_serial_number
+        )
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?
+        .version;
+        let storage_key = format!("maps/{}/{}", robot_serial_number, version);
+
+        sqlx::query!(
+            r#"
+INSERT INTO Maps (robot_serial_number, version, content_type, resolution, origin_x, origin_y, size, storage_key, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+               "#,
+            robot_serial_number,
+            version,
+            content_type,
+            metadata.resolution,
+            metadata.origin_x,
+            metadata.origin_y,
+            data.len() as i64,
+            storage_key,
+            Utc::now()
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        // If the map data can not be stored the row is rolled back
+        storage.put(&storage_key, data).await?;
+        tx.commit()
+            .await
+            .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get_by_version(conn, robot_serial_number, version).await
+    }
+
+    pub async fn get_by_version(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        version: i64,
+    ) -> Result<Self, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT * FROM Maps M
+WHERE M.robot_serial_number = $1 AND M.version = $2
+               "#,
+            robot_serial_number,
+            version
+        )
+        .fetch_one(conn)
+        .await
+        .map(|m| Self {
+            map_id: m.map_id,
+            robot_serial_number: m.robot_serial_number,
+            version: m.version,
+            content_type: m.content_type,
+            metadata: MapMetadata {
+                resolution: m.resolution,
+                origin_x: m.origin_x,
+                origin_y: m.origin_y,
+            },
+            size: m.size,
+            storage_key: m.storage_key,
+            created_at: m.created_at,
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // All of the versions of the robots map, newest first
+    pub async fn get_by_robot(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Vec<Self>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM Maps M
+WHERE M.robot_serial_number = $1
+ORDER BY M.version DESC
+               "#,
+            robot_serial_number
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(results
+            .into_iter()
+            .map(|m| Self {
+                map_id: m.map_id,
+                robot_serial_number: m.robot_serial_number,
+                version: m.version,
+                content_type: m.content_type,
+                metadata: MapMetadata {
+                    resolution: m.resolution,
+                    origin_x: m.origin_x,
+                    origin_y: m.origin_y,
+                },
+                size: m.size,
+                storage_key: m.storage_key,
+                created_at: m.created_at,
+            })
+            .collect())
+    }
+
+    pub async fn data(&self, storage: &impl Storage) -> Result<Vec<u8>, ApiError> {
+        storage.get(&self.storage_key).await
+    }
+}
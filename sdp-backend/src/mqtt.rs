@@ -0,0 +1,242 @@
+// MQTT transport for the robots, built with the mqtt feature and started when MQTT_HOST is set.
+//
+// Each robot publishes to the topics under its serial number:
+//
+//   sdp/robot/{serial}/init       the same JSON as the body sent to /init
+//   sdp/robot/{serial}/poll       the same JSON as the body sent to /poll
+//   sdp/robot/{serial}/telemetry  {"battery_level": 80, "charging": false}
+//
+// and subscribes to sdp/robot/{serial}/command, where the answer to each init and poll is
+// published. This is the same JSON /init and /poll return, or the error if they failed.
+//
+// The broker only lets a robot use the topics for its own serial number, see mosquitto/acl.
+// The serial number in the payload must also match the topic it was published on.
+
+use crate::config;
+use crate::error::ApiError;
+use crate::poll::{Init, Poll, PollResponse};
+use crate::robot::Robot;
+use crate::signing::CommandSigner;
+
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use futures::StreamExt;
+use rumqttc::{Client, Event, Incoming, MqttOptions, QoS};
+use serde::Deserialize;
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const TOPIC_PREFIX: &str = "sdp/robot";
+const CLIENT_ID: &str = "sdp-backend";
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+// How long to wait before reconnecting when the connection to the broker is lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+// The number of requests that can be queued for the broker
+const REQUEST_CAPACITY: usize = 64;
+// The number of messages from all of the robots waiting to be passed to their queues
+const MESSAGE_CAPACITY: usize = 1024;
+// The number of messages from one robot waiting to be handled, more are dropped
+const ROBOT_QUEUE_CAPACITY: usize = 8;
+
+// A message published by a robot
+struct RobotMessage {
+    robot_serial_number: String,
+    kind: MessageKind,
+    payload: Vec<u8>,
+}
+
+enum MessageKind {
+    Init,
+    Poll,
+    Telemetry,
+}
+
+#[derive(Deserialize)]
+struct Telemetry {
+    battery_level: i64,
+    #[serde(default)]
+    charging: bool,
+}
+
+// Connect to the broker and answer the robots. The client runs on its own thread and
+// passes the messages back to be handled on the server's runtime. Messages are dropped
+// when the queues are full, the robot sends them again when it does not get an answer.
+pub fn start(conn: PgPool, signer: CommandSigner, host: String, port: u16) {
+    let mut options = MqttOptions::new(CLIENT_ID, host, port);
+    options.set_keep_alive(KEEP_ALIVE);
+    if let (Some(username), Some(password)) = (config::mqtt_username(), config::mqtt_password()) {
+        options.set_credentials(username, password);
+    }
+
+    let (client, mut connection) = Client::new(options, REQUEST_CAPACITY);
+    let (mut messages, received) = channel(MESSAGE_CAPACITY);
+
+    let subscriber = client.clone();
+    std::thread::spawn(move || {
+        for notification in connection.iter() {
+            match notification {
+                // Subscribe each time we connect, the broker does not keep the subscriptions
+                Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                    for kind in &["init", "poll", "telemetry"] {
+                        let topic = format!("{}/+/{}", TOPIC_PREFIX, kind);
+                        if let Err(e) = subscriber.try_subscribe(topic, QoS::AtLeastOnce) {
+                            log::error!("Could not subscribe to the robot topics {:?}", e);
+                        }
+                    }
+                }
+                Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                    if let Some(message) = RobotMessage::parse(&publish.topic, &publish.payload) {
+                        match messages.try_send(message) {
+                            Ok(()) => (),
+                            Err(e) if e.is_full() => {
+                                log::warn!("Dropped an MQTT message, too many queued")
+                            }
+                            Err(_disconnected) => return,
+                        }
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    log::error!("MQTT connection error {:?}", e);
+                    std::thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+    });
+
+    actix_rt::spawn(dispatch(conn, signer, client, received));
+}
+
+// Pass each message to the queue for its robot, so the robots are answered at the
+// same time and a slow robot does not hold up the others
+async fn dispatch(
+    conn: PgPool,
+    signer: CommandSigner,
+    client: Client,
+    mut received: Receiver<RobotMessage>,
+) {
+    let mut queues: HashMap<String, Sender<RobotMessage>> = HashMap::new();
+
+    while let Some(message) = received.next().await {
+        let queue = queues
+            .entry(message.robot_serial_number.clone())
+            .or_insert_with(|| {
+                let (queue, messages) = channel(ROBOT_QUEUE_CAPACITY);
+                actix_rt::spawn(handle(
+                    conn.clone(),
+                    signer.clone(),
+                    client.clone(),
+                    messages,
+                ));
+                queue
+            });
+
+        if let Err(e) = queue.try_send(message) {
+            log::warn!(
+                "Dropped an MQTT message from {}, too many queued",
+                e.into_inner().robot_serial_number
+            );
+        }
+    }
+}
+
+// Handle the messages for one robot one at a time, so they are handled in order
+async fn handle(
+    conn: PgPool,
+    signer: CommandSigner,
+    client: Client,
+    mut received: Receiver<RobotMessage>,
+) {
+    while let Some(message) = received.next().await {
+        let response = match message.kind {
+            MessageKind::Init => message.init(&conn, &signer).await,
+            MessageKind::Poll => message.poll(&conn, &signer).await,
+            MessageKind::Telemetry => {
+                if let Err(e) = message.telemetry(&conn).await {
+                    log::error!("Could not record telemetry {:?}", e);
+                }
+                continue;
+            }
+        };
+
+        let response_json = match response {
+            Ok(res) => serde_json::to_string(&res),
+            Err(e) => serde_json::to_string(&e),
+        }
+        .unwrap_or_default();
+
+        let topic = format!("{}/{}/command", TOPIC_PREFIX, message.robot_serial_number);
+        if let Err(e) = client.try_publish(topic, QoS::AtLeastOnce, false, response_json) {
+            log::error!("Could not publish the command {:?}", e);
+        }
+    }
+}
+
+impl RobotMessage {
+    // Read a topic of the form "sdp/robot/{serial}/{kind}"
+    fn parse(topic: &str, payload: &[u8]) -> Option<Self> {
+        let mut parts = topic
+            .strip_prefix(TOPIC_PREFIX)?
+            .strip_prefix('/')?
+            .split('/');
+        let robot_serial_number = parts.next()?.to_string();
+        let kind = match parts.next()? {
+            "init" => MessageKind::Init,
+            "poll" => MessageKind::Poll,
+            "telemetry" => MessageKind::Telemetry,
+            _ => return None,
+        };
+        if parts.next().is_some() || robot_serial_number.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            robot_serial_number,
+            kind,
+            payload: payload.to_vec(),
+        })
+    }
+
+    // A robot can only send messages for itself
+    fn check_serial(&self, robot_serial_number: &str) -> Result<(), ApiError> {
+        if self.robot_serial_number == robot_serial_number {
+            Ok(())
+        } else {
+            Err(ApiError::AuthenticationFailed)
+        }
+    }
+
+    async fn init(&self, conn: &PgPool, signer: &CommandSigner) -> Result<PollResponse, ApiError> {
+        let init: Init =
+            serde_json::from_slice(&self.payload).map_err(|_| ApiError::SerializationError)?;
+        self.check_serial(&init.robot_serial_number)?;
+
+        let command = Init::init(conn, &init).await?;
+        PollResponse::new(conn, signer, command).await
+    }
+
+    async fn poll(&self, conn: &PgPool, signer: &CommandSigner) -> Result<PollResponse, ApiError> {
+        let poll: Poll =
+            serde_json::from_slice(&self.payload).map_err(|_| ApiError::SerializationError)?;
+        self.check_serial(&poll.robot_serial_number)?;
+
+        let command = Poll::poll(conn, &poll).await?;
+        PollResponse::new(conn, signer, command).await
+    }
+
+    async fn telemetry(&self, conn: &PgPool) -> Result<(), ApiError> {
+        let telemetry: Telemetry =
+            serde_json::from_slice(&self.payload).map_err(|_| ApiError::SerializationError)?;
+
+        Robot::seen(conn, &self.robot_serial_number).await?;
+        Robot::set_battery(
+            conn,
+            &self.robot_serial_number,
+            telemetry.battery_level,
+            telemetry.charging,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1,12 +1,22 @@
+use actix::Addr;
+use futures::channel::oneshot;
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
+use std::time::{Duration, Instant};
 
-use crate::command::Instruction::{Abort, Idle, Task};
-use crate::command::{Command, Status};
+use crate::battery::BatteryPolicy;
+use crate::command::Instruction::{Abort, Idle, ReturnToDock, Task, UpdateFirmware};
+use crate::command::{AbortReason, Command, Status};
+use crate::config;
+use crate::consumable::Consumable;
 use crate::error::ApiError;
-use crate::robot::Robot;
-
-const MINIMUM_BATTERY_LEVEL: i64 = 50;
+use crate::fault::{Fault, FaultReport};
+use crate::firmware::{FirmwareOffer, FirmwareRelease, FirmwareStatus, FirmwareUpdate};
+use crate::report::{CleaningSession, SessionReport};
+use crate::robot::{Robot, RobotState};
+use crate::signing::{CommandSigner, SignedCommand};
+use crate::stream::{EventHub, WaitForCommand};
+use crate::zone::Zone;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Poll {
@@ -14,21 +24,97 @@ pub struct Poll {
     pub command_id: i64,
     pub status: Status,
     pub battery_level: i64,
+    #[serde(default)]
+    pub charging: bool,
+    #[serde(default)]
+    pub robot_state: RobotState,
+    // The progress of a firmware update, sent while running UpdateFirmware
+    #[serde(default)]
+    pub firmware_status: Option<FirmwareStatus>,
+    // Any faults the robot currently has
+    #[serde(default)]
+    pub faults: Vec<FaultReport>,
+    // If the robot is blocked by an obstacle
+    #[serde(default)]
+    pub obstacle: bool,
+    // The report for the task, sent with the Completed status
+    #[serde(default)]
+    pub report: Option<CleaningSession>,
+    // The number of seconds to hold the poll open waiting for a new command, None to
+    // answer straight away
+    #[serde(default)]
+    pub wait: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Init {
     pub robot_serial_number: String,
     pub battery_level: i64,
+    #[serde(default)]
+    pub charging: bool,
+    #[serde(default)]
+    pub robot_state: RobotState,
+    #[serde(default)]
+    pub firmware_version: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    // The patterns and features the robot supports, e.g. "ZigZag", "Mop" or "Rooms"
+    #[serde(default)]
+    pub capabilities: Option<Vec<String>>,
+}
+
+// The command for the robot, with the zones it needs to carry it out
+// and the signed command the robot checks before carrying it out
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PollResponse {
+    #[serde(flatten)]
+    pub command: Command,
+    pub zones: Vec<Zone>,
+    pub signed: SignedCommand,
+}
+
+impl PollResponse {
+    pub async fn new(
+        conn: &PgPool,
+        signer: &CommandSigner,
+        command: Command,
+    ) -> Result<Self, ApiError> {
+        let zones = Zone::for_command(conn, &command).await?;
+        let signed = signer.sign(&command, &zones)?;
+
+        Ok(Self {
+            command,
+            zones,
+            signed,
+        })
+    }
 }
 
 impl Poll {
     pub async fn poll(conn: &PgPool, poll: &Self) -> Result<Command, ApiError> {
-        // Update the battery value stored in the database
-        Robot::set_battery(conn, &poll.robot_serial_number, poll.battery_level).await?;
+        // Mark the robot as online and update the state and battery value stored in the database
+        Robot::seen(conn, &poll.robot_serial_number).await?;
+        Robot::set_state(conn, &poll.robot_serial_number, &poll.robot_state).await?;
+        let robot = Robot::set_battery(
+            conn,
+            &poll.robot_serial_number,
+            poll.battery_level,
+            poll.charging || poll.robot_state.is_charging(),
+        )
+        .await?;
+        let policy = robot.battery_policy();
 
         // Get the current command from the database
-        let current_command = match Command::get_by_id(conn, poll.command_id).await {
+        let current_command = Command::get_by_id(conn, poll.command_id).await;
+
+        // Store the faults against the command the robot was running
+        let command_id = current_command.as_ref().ok().map(|c| c.command_id);
+        for report in &poll.faults {
+            Fault::report(conn, &poll.robot_serial_number, command_id, report).await?;
+        }
+        Fault::clear_missing(conn, &poll.robot_serial_number, &poll.faults).await?;
+
+        let current_command = match current_command {
             Ok(c) if c.status.cancelled() => {
                 match Command::pending(conn, &poll.robot_serial_number).await? {
                     Some(abort) => return Ok(abort),
@@ -40,37 +126,87 @@ impl Poll {
         };
 
         match &current_command.instruction {
+            // If the battery runs low during a task, abort it and remember the task
+            // so it can be resumed once the robot has charged
+            Task(..) if !poll.status.is_completed() && policy.should_abort(poll.battery_level) => {
+                current_command.low_battery_interrupt(conn).await
+            }
+
+            // If the robot is blocked pause the task, cancelling it if the
+            // obstacle has not been cleared within the timeout
+            Task(..) if poll.obstacle && current_command.obstacle_timed_out() => {
+                current_command.obstacle_cancel(conn).await?;
+                poll.next_command(conn, &policy).await
+            }
+            Task(..) if poll.obstacle => current_command.obstacle_pause(conn).await,
+
+            // Once the obstacle has been cleared carry on with the task
+            Task(..) if current_command.paused_by_obstacle() && !poll.status.is_completed() => {
+                current_command.obstacle_cleared(conn).await
+            }
+
             // If we are doing a task and it had not completed,
             // update the status and keep doing it
-            Task(_) if !poll.status.is_completed() => {
+            Task(..) if !poll.status.is_completed() => {
                 current_command.update_status(conn, &poll.status).await
             }
 
-            // If we are doing a task and it has completed do the next
-            Task(_) if poll.status.is_completed() => {
-                current_command.update_status(conn, &poll.status).await?;
-                match Command::pending(conn, &poll.robot_serial_number).await? {
-                    Some(c) => Ok(c.in_progress(conn).await?),
-                    None => {
-                        Command::new_idle(conn, &poll.robot_serial_number)
-                            .await?
-                            .in_progress(conn)
-                            .await
-                    }
+            // If we are doing a task and it has completed store the report and do the next
+            Task(..) if poll.status.is_completed() => {
+                // The report and wear are only stored the first time, a robot that
+                // did not get the answer sends the completed poll again
+                let (_, completed) = current_command
+                    .update_status_changed(conn, &poll.status)
+                    .await?;
+                if completed {
+                    poll.record_completed(conn, &current_command).await;
                 }
+                poll.next_command(conn, &policy).await
             }
 
             // If we are idle check that the battery level is valid
-            Idle if !poll.check_battery().await => current_command.low_battery_abort(conn).await,
+            Idle if !policy.can_start(poll.battery_level) => {
+                current_command.low_battery_abort(conn).await
+            }
+
+            // If we are idle and battery is ok check for commands, if there are none
+            // offer a firmware update, otherwise stay idle
+            Idle => {
+                let next = match poll.next_pending(conn, &policy).await? {
+                    Some(c) => Some(c),
+                    None => poll.firmware_offer(conn, &robot, &policy).await?,
+                };
 
-            // If we are idle and battery is ok check for commands, if there are non stay idle
-            Idle => match Command::pending(conn, &poll.robot_serial_number).await? {
-                Some(c) => {
-                    current_command.completed(conn).await?;
-                    Ok(c.in_progress(conn).await?)
+                match next {
+                    Some(c) => {
+                        current_command.completed(conn).await?;
+                        Ok(c.in_progress(conn).await?)
+                    }
+                    None => Ok(current_command),
                 }
-                None => Ok(current_command),
-            },
+            }
+
+            // Once the robot has docked and charged past the resume level,
+            // go back to the interrupted task or the next command
+            ReturnToDock if poll.status.is_completed() && policy.can_resume(poll.battery_level) => {
+                current_command.completed(conn).await?;
+                poll.next_command(conn, &policy).await
+            }
+
+            // Otherwise keep going back to the dock and charging
+            ReturnToDock => current_command.update_status(conn, &poll.status).await,
+
+            // Robots may still have a low battery abort from before they were sent to the dock
+            Abort(AbortReason::LowBattery) if policy.can_resume(poll.battery_level) => {
+                current_command.completed(conn).await?;
+                poll.next_command(conn, &policy).await
+            }
+
+            // Track the progress of the update, once it has finished carry on as normal
+            UpdateFirmware(offer) => {
+                poll.firmware_progress(conn, &current_command, offer, &policy)
+                    .await
+            }
 
             // If we get the Abort instruction, just update the status of the command
             Abort(_) => current_command.update_status(conn, &poll.status).await,
@@ -78,23 +214,181 @@ impl Poll {
         }
     }
 
-    /// Checks the current battery level of the Robot
-    ///
-    /// If the battery level is not sufficent the robot will
-    /// be told to abort due to low battery.
-    async fn check_battery(&self) -> bool {
-        check_battery(self.battery_level)
+    // Poll, holding the request open until the robot has a different command or the wait
+    // runs out. A command being created or changing status wakes the poll to check again.
+    pub async fn long_poll(
+        conn: &PgPool,
+        hub: &Addr<EventHub>,
+        poll: &Self,
+    ) -> Result<Command, ApiError> {
+        let wait = match poll.wait {
+            Some(w) if w > 0 => w.min(config::long_poll_max_wait()),
+            _ => return Self::poll(conn, poll).await,
+        };
+        let deadline = Instant::now() + Duration::from_secs(wait as u64);
+
+        let mut poll = poll.clone();
+        loop {
+            // Start waiting before polling so changes made in between still wake the poll
+            let (waker, woken) = oneshot::channel();
+            hub.do_send(WaitForCommand {
+                robot_serial_number: poll.robot_serial_number.clone(),
+                waker,
+            });
+
+            let command = Self::poll(conn, &poll).await?;
+            if command.command_id != poll.command_id {
+                return Ok(command);
+            }
+
+            // The report has been recorded, it is not needed again. The faults are kept
+            // as the robot is still reporting them, so they are not marked as cleared.
+            poll.report = None;
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match actix_rt::time::timeout(remaining, woken).await {
+                Ok(Ok(())) => continue,
+                _timed_out => return Ok(command),
+            }
+        }
+    }
+
+    // The command that should be run next, a task interrupted by low battery is
+    // resumed first, but only once the battery is over the resume level
+    async fn next_pending(
+        &self,
+        conn: &PgPool,
+        policy: &BatteryPolicy,
+    ) -> Result<Option<Command>, ApiError> {
+        if policy.can_resume(self.battery_level) {
+            if let Some(c) = Command::resume_interrupted(conn, &self.robot_serial_number).await? {
+                return Ok(Some(c));
+            }
+        }
+
+        Command::pending(conn, &self.robot_serial_number).await
+    }
+
+    // Make a firmware update command if there is a release for the robot, updates
+    // are only offered while the robot is on the dock and charged
+    async fn firmware_offer(
+        &self,
+        conn: &PgPool,
+        robot: &Robot,
+        policy: &BatteryPolicy,
+    ) -> Result<Option<Command>, ApiError> {
+        let docked = matches!(robot.state, RobotState::Docked | RobotState::Charging);
+        if !docked || !policy.can_resume(self.battery_level) {
+            return Ok(None);
+        }
+
+        let release = match FirmwareRelease::available(conn, robot).await? {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+
+        let offer = release.offer();
+        FirmwareUpdate::set(
+            conn,
+            &self.robot_serial_number,
+            release.release_id,
+            &FirmwareStatus::Offered,
+        )
+        .await?;
+        Command::new_firmware_update(conn, &self.robot_serial_number, offer)
+            .await
+            .map(Some)
     }
-}
 
-fn check_battery(battery_level: i64) -> bool {
-    (battery_level >= 0 && battery_level <= 100) && (battery_level > MINIMUM_BATTERY_LEVEL)
+    // Record the firmware status reported by the robot
+    async fn firmware_progress(
+        &self,
+        conn: &PgPool,
+        current_command: &Command,
+        offer: &FirmwareOffer,
+        policy: &BatteryPolicy,
+    ) -> Result<Command, ApiError> {
+        let status = self
+            .firmware_status
+            .clone()
+            .unwrap_or(FirmwareStatus::Offered);
+        FirmwareUpdate::set(conn, &self.robot_serial_number, offer.release_id, &status).await?;
+
+        if status == FirmwareStatus::Succeeded {
+            Robot::set_capabilities(
+                conn,
+                &self.robot_serial_number,
+                Some(&offer.version),
+                None,
+                None,
+            )
+            .await?;
+        }
+
+        if status.is_finished() {
+            current_command.completed(conn).await?;
+            self.next_command(conn, policy).await
+        } else {
+            current_command.update_status(conn, &self.status).await
+        }
+    }
+
+    // Store the report the robot sent for the completed task and the wear on its components
+    async fn record_completed(&self, conn: &PgPool, command: &Command) {
+        // A report that is not valid is dropped, the task is still completed
+        let report = match &self.report {
+            Some(session) => match SessionReport::record(conn, command, session).await {
+                Ok(_) => Some(session),
+                Err(e) => {
+                    log::error!("Could not record the session report {:?}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let duration = report.map(|r| r.duration);
+        // The task is still completed if the wear on the components can not be recorded
+        if let Err(e) = Consumable::record_task(conn, command, duration).await {
+            log::error!("Could not record the consumable runtime {:?}", e);
+        }
+    }
+
+    // Start the next command, or become idle if there is nothing to do
+    async fn next_command(
+        &self,
+        conn: &PgPool,
+        policy: &BatteryPolicy,
+    ) -> Result<Command, ApiError> {
+        match self.next_pending(conn, policy).await? {
+            Some(c) => Ok(c.in_progress(conn).await?),
+            None => {
+                Command::new_idle(conn, &self.robot_serial_number)
+                    .await?
+                    .in_progress(conn)
+                    .await
+            }
+        }
+    }
 }
 
 // When the robot first turns on it will have no knowledge of previous communications
 impl Init {
     pub async fn init(conn: &PgPool, init: &Self) -> Result<Command, ApiError> {
-        if !init.check_battery() {
+        Robot::initialised(conn, &init.robot_serial_number).await?;
+        let robot = Robot::set_capabilities(
+            conn,
+            &init.robot_serial_number,
+            init.firmware_version.as_deref(),
+            init.model.as_deref(),
+            init.capabilities.as_deref(),
+        )
+        .await?;
+        let policy = robot.battery_policy();
+
+        // The robot restarts after installing firmware, check if the update worked
+        FirmwareUpdate::check_installed(conn, &robot).await?;
+
+        if !policy.can_start(init.battery_level) {
             return Command::new_low_battery(conn, &init.robot_serial_number).await;
         }
 
@@ -108,12 +402,15 @@ impl Init {
             command_id: polling_command.command_id,
             status: polling_command.status,
             battery_level: init.battery_level,
+            charging: init.charging,
+            robot_state: init.robot_state.clone(),
+            firmware_status: None,
+            faults: Vec::new(),
+            obstacle: false,
+            report: None,
+            wait: None,
         };
 
         Poll::poll(conn, &poll).await
     }
-
-    fn check_battery(&self) -> bool {
-        check_battery(self.battery_level)
-    }
 }
@@ -0,0 +1,181 @@
+use crate::command::Command;
+use crate::error::ApiError;
+use chrono::{serde::ts_seconds, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+// The longest a task can report it ran for, in seconds
+const MAX_SESSION_DURATION: i64 = 24 * 60 * 60;
+
+// What happened during a task, sent by the robot when the task is completed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CleaningSession {
+    // How long the task ran for, in seconds
+    pub duration: i64,
+    // The area cleaned, in square meters
+    pub area_covered: f64,
+    // The percentage of the battery used
+    pub battery_used: i64,
+    // How many times the task was paused
+    #[serde(default)]
+    pub pauses: i64,
+    // How many faults the robot had during the task
+    #[serde(default)]
+    pub faults: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionReport {
+    pub command_id: i64,
+    pub robot_serial_number: String,
+    #[serde(flatten)]
+    pub session: CleaningSession,
+    #[serde(with = "ts_seconds")]
+    pub completed_at: chrono::DateTime<Utc>,
+}
+
+// The periods the session statistics can be grouped by
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsPeriod {
+    #[default]
+    Week,
+    Month,
+}
+
+// The totals for the sessions completed in a week or month
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionStats {
+    #[serde(with = "ts_seconds")]
+    pub period_start: chrono::DateTime<Utc>,
+    pub sessions: i64,
+    pub area_covered: f64,
+    // The total time spent cleaning, in seconds
+    pub runtime: i64,
+}
+
+impl CleaningSession {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let valid_area = self.area_covered.is_finite() && self.area_covered >= 0.0;
+        let valid_battery = (0..=100).contains(&self.battery_used);
+
+        let valid_duration = (0..=MAX_SESSION_DURATION).contains(&self.duration);
+
+        if valid_duration && valid_area && valid_battery && self.pauses >= 0 && self.faults >= 0 {
+            Ok(())
+        } else {
+            Err(ApiError::InvalidSessionReport)
+        }
+    }
+}
+
+impl SessionReport {
+    // Store the report for the command, replacing any report already sent for it
+    pub async fn record(
+        conn: &PgPool,
+        command: &Command,
+        session: &CleaningSession,
+    ) -> Result<Self, ApiError> {
+        session.validate()?;
+        let completed_at = Utc::now();
+
+        sqlx::query!(
+            r#"
+INSERT INTO SessionReports (command_id, robot_serial_number, duration, area_covered, battery_used, pauses, faults, completed_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT (command_id)
+DO UPDATE SET duration = $3, area_covered = $4, battery_used = $5, pauses = $6, faults = $7, completed_at = $8
+               "#,
+            command.command_id,
+            command.robot_serial_number,
+            session.duration,
+            session.area_covered,
+            session.battery_used,
+            session.pauses,
+            session.faults,
+            completed_at
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(Self {
+            command_id: command.command_id,
+            robot_serial_number: command.robot_serial_number.clone(),
+            session: session.clone(),
+            completed_at,
+        })
+    }
+
+    pub async fn get_by_command(conn: &PgPool, command_id: i64) -> Result<Option<Self>, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT * FROM SessionReports R
+WHERE R.command_id = $1
+               "#,
+            command_id
+        )
+        .fetch_optional(conn)
+        .await
+        .map(|r| {
+            r.map(|r| Self {
+                command_id: r.command_id,
+                robot_serial_number: r.robot_serial_number,
+                session: CleaningSession {
+                    duration: r.duration,
+                    area_covered: r.area_covered,
+                    battery_used: r.battery_used,
+                    pauses: r.pauses,
+                    faults: r.faults,
+                },
+                completed_at: r.completed_at,
+            })
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // The total area and runtime of the robots sessions in each week or month, oldest first
+    pub async fn stats(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        period: StatsPeriod,
+    ) -> Result<Vec<SessionStats>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT date_trunc($2, R.completed_at) AS "period_start!",
+       COUNT(*) AS "sessions!",
+       SUM(R.area_covered) AS "area_covered!",
+       SUM(R.duration)::BIGINT AS "runtime!"
+FROM SessionReports R
+WHERE R.robot_serial_number = $1
+GROUP BY 1
+ORDER BY 1
+               "#,
+            robot_serial_number,
+            period.to_string()
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(results
+            .into_iter()
+            .map(|s| SessionStats {
+                period_start: s.period_start,
+                sessions: s.sessions,
+                area_covered: s.area_covered,
+                runtime: s.runtime,
+            })
+            .collect())
+    }
+}
+
+// The names used by date_trunc
+impl std::fmt::Display for StatsPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatsPeriod::Week => write!(f, "week"),
+            StatsPeriod::Month => write!(f, "month"),
+        }
+    }
+}
@@ -1,51 +1,145 @@
+use crate::battery::BatteryReading;
+use crate::command::TimeTolerance;
 use crate::error::ApiError;
+use crate::event::{BatteryChange, Event, RobotEvent};
+use chrono::{serde::ts_seconds_option, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
 
+mod battery;
+mod model;
+mod presence;
+
+pub use battery::BatteryPolicyOverride;
+pub use model::RobotModel;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum RobotState {
+    // Away from the dock, working or idle
+    #[default]
+    Active,
+    // On the dock and fully charged
+    Docked,
+    // On the dock and charging
+    Charging,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Robot {
     pub robot_serial_number: String,
     pub battery_level: i64,
+    // If the robot was charging at the last poll
+    #[serde(default)]
+    pub charging: bool,
     pub assigned: bool,
+    #[serde(default)]
+    pub state: RobotState,
+    // The hardware model, this decides the cleaning patterns the robot can run
+    #[serde(default)]
+    pub model: Option<String>,
+    // Reported by the robot when it initialises, the capabilities are None
+    // until the robot has reported them
+    #[serde(default)]
+    pub firmware_version: Option<String>,
+    #[serde(default)]
+    pub capabilities: Option<Vec<String>>,
+    // Overrides for the global scheduling tolerances, in seconds
+    pub time_issued_tolerance: Option<i64>,
+    pub instruction_grace_period: Option<i64>,
+    // Overrides for the global battery policy
+    pub battery_start_level: Option<i64>,
+    pub battery_abort_level: Option<i64>,
+    pub battery_resume_level: Option<i64>,
+    // When the robot last polled and last initialised
+    #[serde(default, with = "ts_seconds_option")]
+    pub last_seen_at: Option<chrono::DateTime<Utc>>,
+    #[serde(default, with = "ts_seconds_option")]
+    pub last_init_at: Option<chrono::DateTime<Utc>>,
+    // If the robot has polled within the offline threshold
+    #[serde(default)]
+    pub online: bool,
 }
 
 impl Robot {
+    // Update the current battery level and keep the reading in the battery history
     pub async fn set_battery(
         conn: &PgPool,
         robot_serial_number: &str,
         battery_level: i64,
+        charging: bool,
     ) -> Result<Self, ApiError> {
-        sqlx::query!(
+        BatteryReading::record(conn, robot_serial_number, battery_level, charging).await?;
+
+        // The row is locked while the previous values are read, so a change is only
+        // reported once when the robot polls twice at the same time
+        let previous = sqlx::query!(
             r#"
-UPDATE Robot
-SET battery_level = $2
-WHERE robot_serial_number = $1
+UPDATE Robot R
+SET battery_level = $2,
+    charging = $3
+FROM (SELECT battery_level, charging FROM Robot WHERE robot_serial_number = $1 FOR UPDATE) P
+WHERE R.robot_serial_number = $1
+RETURNING P.battery_level, P.charging
                "#,
-            robot_serial_number.clone(),
-            battery_level
+            robot_serial_number,
+            battery_level,
+            charging
         )
-        .execute(conn)
+        .fetch_one(conn)
         .await
         .map_err(|_| ApiError::DatabaseConnFailed)?;
 
+        // Only tell the app about the battery when it has changed
+        let changed = previous.battery_level != battery_level || previous.charging != charging;
+        if changed {
+            Event::record(
+                conn,
+                robot_serial_number,
+                chrono::Utc::now(),
+                &RobotEvent::Battery(BatteryChange {
+                    battery_level,
+                    charging,
+                }),
+            )
+            .await?;
+        }
+
         Self::get_by_serial(conn, robot_serial_number).await
     }
 
-    pub async fn new(conn: &PgPool, robot_serial_number: &str) -> Result<Self, ApiError> {
+    pub async fn new(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        model: Option<&str>,
+    ) -> Result<Self, ApiError> {
         sqlx::query!(
             r#"
-INSERT INTO Robot (robot_serial_number)
-VALUES ($1)
+INSERT INTO Robot (robot_serial_number, model)
+VALUES ($1, $2)
 RETURNING robot_serial_number
         "#,
             &robot_serial_number,
+            model,
         )
         .fetch_one(conn)
         .await
         .map(|_| Self {
             robot_serial_number: robot_serial_number.to_string(),
             battery_level: 0,
+            charging: false,
             assigned: false,
+            state: RobotState::Active,
+            model: model.map(|m| m.to_string()),
+            firmware_version: None,
+            capabilities: None,
+            time_issued_tolerance: None,
+            instruction_grace_period: None,
+            battery_start_level: None,
+            battery_abort_level: None,
+            battery_resume_level: None,
+            last_seen_at: None,
+            last_init_at: None,
+            online: false,
         })
         .map_err(|_| ApiError::RobotInitializationFailed)
     }
@@ -63,11 +157,57 @@ WHERE R.robot_serial_number = $1
         .map(|r| Self {
             robot_serial_number: r.robot_serial_number,
             battery_level: r.battery_level,
+            charging: r.charging,
             assigned: r.assigned,
+            state: r.state.into(),
+            model: r.model,
+            firmware_version: r.firmware_version,
+            capabilities: r.capabilities,
+            time_issued_tolerance: r.time_issued_tolerance,
+            instruction_grace_period: r.instruction_grace_period,
+            battery_start_level: r.battery_start_level,
+            battery_abort_level: r.battery_abort_level,
+            battery_resume_level: r.battery_resume_level,
+            last_seen_at: r.last_seen_at,
+            last_init_at: r.last_init_at,
+            online: Self::is_online(r.last_seen_at),
         })
         .map_err(|_| ApiError::DatabaseConnFailed)
     }
 
+    pub async fn set_state(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        state: &RobotState,
+    ) -> Result<Self, ApiError> {
+        let previous = Self::get_by_serial(conn, robot_serial_number).await?;
+
+        sqlx::query!(
+            r#"
+UPDATE Robot
+SET state = $2
+WHERE robot_serial_number = $1
+               "#,
+            robot_serial_number,
+            state.to_string()
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        if previous.state != *state {
+            Event::record(
+                conn,
+                robot_serial_number,
+                chrono::Utc::now(),
+                &RobotEvent::StateChanged(state.clone()),
+            )
+            .await?;
+        }
+
+        Self::get_by_serial(conn, robot_serial_number).await
+    }
+
     // Assigning the robot to a user, making assigned true
     pub async fn assign(&self, conn: &PgPool) -> Result<Self, ApiError> {
         // Check if the robot already assigned
@@ -90,4 +230,73 @@ WHERE robot_serial_number = $1
 
         Self::get_by_serial(conn, &self.robot_serial_number).await
     }
+
+    // Set the robot specific scheduling tolerances, None will use the global value
+    pub async fn set_time_tolerance(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        time_issued_tolerance: Option<i64>,
+        instruction_grace_period: Option<i64>,
+    ) -> Result<Self, ApiError> {
+        TimeTolerance::check_override(time_issued_tolerance)?;
+        TimeTolerance::check_override(instruction_grace_period)?;
+
+        sqlx::query!(
+            r#"
+UPDATE Robot
+SET time_issued_tolerance = $2,
+    instruction_grace_period = $3
+WHERE robot_serial_number = $1
+               "#,
+            robot_serial_number,
+            time_issued_tolerance,
+            instruction_grace_period
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get_by_serial(conn, robot_serial_number).await
+    }
+
+    // The scheduling tolerances for this robot, using the global values
+    // where the robot does not override them
+    pub fn time_tolerance(&self) -> TimeTolerance {
+        let default = TimeTolerance::default();
+
+        TimeTolerance {
+            time_issued: self
+                .time_issued_tolerance
+                .map_or(default.time_issued, Duration::seconds),
+            grace_period: self
+                .instruction_grace_period
+                .map_or(default.grace_period, Duration::seconds),
+        }
+    }
+}
+
+impl RobotState {
+    pub fn is_charging(&self) -> bool {
+        self == &Self::Charging
+    }
+}
+
+impl std::fmt::Display for RobotState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RobotState::Active => write!(f, "RobotState::Active"),
+            RobotState::Docked => write!(f, "RobotState::Docked"),
+            RobotState::Charging => write!(f, "RobotState::Charging"),
+        }
+    }
+}
+
+impl From<String> for RobotState {
+    fn from(state: String) -> Self {
+        match &state[..] {
+            "RobotState::Docked" => RobotState::Docked,
+            "RobotState::Charging" => RobotState::Charging,
+            _ => RobotState::Active,
+        }
+    }
 }
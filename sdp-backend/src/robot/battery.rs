@@ -0,0 +1,64 @@
+use crate::battery::BatteryPolicy;
+use crate::error::ApiError;
+use crate::robot::Robot;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+// The robot specific battery levels, a level that is None uses the global value
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatteryPolicyOverride {
+    pub start_level: Option<i64>,
+    pub abort_level: Option<i64>,
+    pub resume_level: Option<i64>,
+}
+
+impl Robot {
+    // The battery policy for this robot, using the global values
+    // where the robot does not override them
+    pub fn battery_policy(&self) -> BatteryPolicy {
+        let default = BatteryPolicy::default();
+
+        BatteryPolicy {
+            start_level: self.battery_start_level.unwrap_or(default.start_level),
+            abort_level: self.battery_abort_level.unwrap_or(default.abort_level),
+            resume_level: self.battery_resume_level.unwrap_or(default.resume_level),
+        }
+    }
+
+    // Set the robot specific battery levels, the resulting policy must be valid
+    pub async fn set_battery_policy(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        policy: &BatteryPolicyOverride,
+    ) -> Result<Self, ApiError> {
+        let robot = Self::get_by_serial(conn, robot_serial_number).await?;
+
+        Self {
+            battery_start_level: policy.start_level,
+            battery_abort_level: policy.abort_level,
+            battery_resume_level: policy.resume_level,
+            ..robot
+        }
+        .battery_policy()
+        .validate()?;
+
+        sqlx::query!(
+            r#"
+UPDATE Robot
+SET battery_start_level = $2,
+    battery_abort_level = $3,
+    battery_resume_level = $4
+WHERE robot_serial_number = $1
+               "#,
+            robot_serial_number,
+            policy.start_level,
+            policy.abort_level,
+            policy.resume_level
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get_by_serial(conn, robot_serial_number).await
+    }
+}
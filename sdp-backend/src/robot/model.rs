@@ -0,0 +1,182 @@
+use crate::command::{CleaningPattern, Instruction, TaskParameters};
+use crate::error::ApiError;
+use crate::robot::Robot;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+// The patterns that robots without a known model can run
+const DEFAULT_PATTERNS: [&str; 2] = ["ZigZag", "Circular"];
+
+// The cleaning patterns a model of robot is able to run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RobotModel {
+    pub model: String,
+    pub patterns: Vec<String>,
+}
+
+impl RobotModel {
+    pub async fn get(conn: &PgPool, model: &str) -> Result<Self, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT M.pattern FROM ModelPatterns M
+WHERE M.model = $1
+               "#,
+            model
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(Self {
+            model: model.to_string(),
+            patterns: results.into_iter().map(|r| r.pattern).collect(),
+        })
+    }
+
+    // Replace the patterns supported by the model
+    pub async fn set_patterns(
+        conn: &PgPool,
+        model: &str,
+        patterns: &[String],
+    ) -> Result<Self, ApiError> {
+        if patterns
+            .iter()
+            .any(|p| !CleaningPattern::NAMES.contains(&&p[..]))
+        {
+            return Err(ApiError::CmdInstructionNotSupported);
+        }
+
+        // The patterns are replaced together, so the model is never left with only some of them
+        let mut tx = conn
+            .begin()
+            .await
+            .map_err(|_| ApiError::DatabaseConnFailed)?;
+        sqlx::query!(
+            r#"
+DELETE FROM ModelPatterns
+WHERE model = $1
+               "#,
+            model
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        for pattern in patterns {
+            sqlx::query!(
+                r#"
+INSERT INTO ModelPatterns (model, pattern)
+VALUES ($1, $2)
+ON CONFLICT DO NOTHING
+               "#,
+                model,
+                pattern
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|_| ApiError::DatabaseConnFailed)?;
+        }
+        tx.commit()
+            .await
+            .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get(conn, model).await
+    }
+}
+
+impl Robot {
+    // The cleaning patterns this robot can run. The capabilities the robot reported
+    // are used if it has reported them, otherwise the patterns for its model.
+    pub async fn supported_patterns(&self, conn: &PgPool) -> Result<Vec<String>, ApiError> {
+        if let Some(capabilities) = &self.capabilities {
+            return Ok(CleaningPattern::NAMES
+                .iter()
+                .filter(|p| capabilities.iter().any(|c| c == *p))
+                .map(|p| p.to_string())
+                .collect());
+        }
+
+        let patterns = match &self.model {
+            Some(model) => RobotModel::get(conn, model).await?.patterns,
+            None => Vec::new(),
+        };
+
+        if patterns.is_empty() {
+            Ok(DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect())
+        } else {
+            Ok(patterns)
+        }
+    }
+
+    // Store the firmware, model and capabilities the robot reported when it initialised
+    pub async fn set_capabilities(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        firmware_version: Option<&str>,
+        model: Option<&str>,
+        capabilities: Option<&[String]>,
+    ) -> Result<Self, ApiError> {
+        let robot = Self::get_by_serial(conn, robot_serial_number).await?;
+
+        // Only replace the values the robot has reported
+        let firmware_version = firmware_version
+            .map(|f| f.to_string())
+            .or(robot.firmware_version);
+        let model = model.map(|m| m.to_string()).or(robot.model);
+        let capabilities = capabilities.map(|c| c.to_vec()).or(robot.capabilities);
+
+        sqlx::query!(
+            r#"
+UPDATE Robot
+SET firmware_version = $2,
+    model = $3,
+    capabilities = $4
+WHERE robot_serial_number = $1
+               "#,
+            robot_serial_number,
+            firmware_version,
+            model,
+            capabilities.as_deref()
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get_by_serial(conn, robot_serial_number).await
+    }
+
+    // Check the robot is able to run the instruction, so it is never sent a
+    // pattern it does not know. The capabilities the robot reported are used
+    // if it has reported them, otherwise the patterns for its model. The built
+    // in instructions are always allowed.
+    pub async fn check_instruction(
+        &self,
+        conn: &PgPool,
+        instruction: &Instruction,
+        parameters: &Option<TaskParameters>,
+    ) -> Result<(), ApiError> {
+        if let Some(capabilities) = &self.capabilities {
+            return if instruction
+                .required_capabilities(parameters)
+                .iter()
+                .all(|r| capabilities.iter().any(|c| c == r))
+            {
+                Ok(())
+            } else {
+                Err(ApiError::CmdInstructionNotSupported)
+            };
+        }
+
+        match instruction {
+            Instruction::Task(pattern) => {
+                let patterns = self.supported_patterns(conn).await?;
+                if patterns.iter().any(|p| p == pattern.name()) {
+                    Ok(())
+                } else {
+                    Err(ApiError::CmdInstructionNotSupported)
+                }
+            }
+            _supported => Ok(()),
+        }
+    }
+}
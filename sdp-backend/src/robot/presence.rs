@@ -0,0 +1,119 @@
+use crate::config;
+use crate::error::ApiError;
+use crate::event::{Event, RobotEvent};
+use crate::robot::Robot;
+use chrono::{Duration, Utc};
+use sqlx::postgres::PgPool;
+
+impl Robot {
+    // A robot is online if it has polled within the offline threshold
+    pub fn is_online(last_seen_at: Option<chrono::DateTime<Utc>>) -> bool {
+        let threshold = Duration::seconds(config::robot_offline_threshold());
+
+        match last_seen_at {
+            Some(t) => chrono::Utc::now() - t <= threshold,
+            None => false,
+        }
+    }
+
+    // Record that the robot has polled the server, marking it as online
+    pub async fn seen(conn: &PgPool, robot_serial_number: &str) -> Result<Self, ApiError> {
+        let robot = Self::get_by_serial(conn, robot_serial_number).await?;
+        let time_now = chrono::Utc::now();
+
+        // If the robot was offline, make sure that was recorded before it comes back
+        if !robot.online {
+            robot.refresh_presence(conn).await?;
+            Event::record(conn, robot_serial_number, time_now, &RobotEvent::Online).await?;
+        }
+
+        sqlx::query!(
+            r#"
+UPDATE Robot
+SET last_seen_at = $2
+WHERE robot_serial_number = $1
+               "#,
+            robot_serial_number,
+            time_now
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get_by_serial(conn, robot_serial_number).await
+    }
+
+    // Record that the robot has initialised, this also counts as it being seen
+    pub async fn initialised(conn: &PgPool, robot_serial_number: &str) -> Result<Self, ApiError> {
+        Self::seen(conn, robot_serial_number).await?;
+
+        sqlx::query!(
+            r#"
+UPDATE Robot
+SET last_init_at = last_seen_at
+WHERE robot_serial_number = $1
+               "#,
+            robot_serial_number
+        )
+        .execute(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Self::get_by_serial(conn, robot_serial_number).await
+    }
+
+    // The robot can only be noticed to be offline when it is looked at, so if it was
+    // last recorded as online and has since missed the threshold, record it going offline
+    // at the time the threshold passed.
+    pub async fn refresh_presence(&self, conn: &PgPool) -> Result<Self, ApiError> {
+        let last_presence = Event::last_presence(conn, &self.robot_serial_number).await?;
+
+        if let (false, Some(RobotEvent::Online), Some(last_seen_at)) =
+            (self.online, last_presence, self.last_seen_at)
+        {
+            let went_offline = last_seen_at + Duration::seconds(config::robot_offline_threshold());
+            Event::record(
+                conn,
+                &self.robot_serial_number,
+                went_offline,
+                &RobotEvent::Offline,
+            )
+            .await?;
+        }
+
+        Ok(self.clone())
+    }
+
+    // Record every robot that has gone offline since it was last looked at, so the
+    // Offline event is sent without waiting for a user to look at the robot
+    pub async fn record_offline(conn: &PgPool) -> Result<(), ApiError> {
+        let went_offline_before =
+            chrono::Utc::now() - Duration::seconds(config::robot_offline_threshold());
+
+        let results = sqlx::query!(
+            r#"
+SELECT R.robot_serial_number FROM Robot R
+WHERE R.last_seen_at < $1 AND
+      (SELECT E.event FROM Events E
+       WHERE E.robot_serial_number = R.robot_serial_number AND
+            (E.event = 'RobotEvent::Online' OR E.event = 'RobotEvent::Offline')
+       ORDER BY E.time DESC, E.event_id DESC
+       LIMIT 1) = 'RobotEvent::Online'
+               "#,
+            went_offline_before
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        for r in results {
+            // The robot may have polled or been removed since, this is
+            // checked again the next time round
+            if let Ok(robot) = Self::get_by_serial(conn, &r.robot_serial_number).await {
+                robot.refresh_presence(conn).await.ok();
+            }
+        }
+
+        Ok(())
+    }
+}
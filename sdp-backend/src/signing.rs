@@ -0,0 +1,136 @@
+use crate::command::{Command, Instruction, TaskParameters};
+use crate::config;
+use crate::error::ApiError;
+use crate::zone::Zone;
+use ed25519_dalek::{Signer, SigningKey, SECRET_KEY_LENGTH};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::Path;
+
+// Expiry times are rounded to this many seconds, so the response to a robot that polls
+// again without anything changing is the same and can be answered as unchanged
+const EXPIRY_ROUNDING: i64 = 60;
+
+// Signs the commands sent to the robots so they can check a command came from this
+// server and has not been changed or replayed. Robots are given the public key from
+// GET /robot/signing-key when they are set up.
+#[derive(Clone)]
+pub struct CommandSigner {
+    key: SigningKey,
+}
+
+// What is signed for a command. The payload is sent as this JSON, the robot checks the
+// signature of the payload, that the serial number is its own and that it has not expired,
+// then carries out the instruction in the payload and keeps to the zones in the payload.
+#[derive(Serialize, Debug)]
+struct CommandClaims<'a> {
+    robot_serial_number: &'a str,
+    command_id: i64,
+    instruction: &'a Instruction,
+    parameters: &'a Option<TaskParameters>,
+    zones: &'a [Zone],
+    // Seconds since the epoch after which the robot should not carry out the command
+    expires_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignedCommand {
+    pub payload: String,
+    // The Ed25519 signature of the payload, in base64
+    pub signature: String,
+    pub key_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PublicKey {
+    pub algorithm: String,
+    // The Ed25519 public key, in base64
+    pub public_key: String,
+    pub key_id: String,
+}
+
+impl CommandSigner {
+    // Use the key in SIGNING_KEY, otherwise the key in the key file, making one the first
+    // time the server is run
+    pub fn load(key_file: &str) -> Result<Self, ApiError> {
+        let secret = match config::signing_key() {
+            Some(key) => base64::decode(key).map_err(|_| ApiError::InvalidSigningKey)?,
+            None => match fs::read(key_file) {
+                Ok(secret) => secret,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    let key = SigningKey::generate(&mut OsRng);
+                    write_key_file(key_file, key.as_bytes())
+                        .map_err(|_| ApiError::StorageFailed)?;
+                    key.to_bytes().to_vec()
+                }
+                Err(_) => return Err(ApiError::StorageFailed),
+            },
+        };
+
+        let secret: [u8; SECRET_KEY_LENGTH] = secret
+            .as_slice()
+            .try_into()
+            .map_err(|_| ApiError::InvalidSigningKey)?;
+
+        Ok(Self {
+            key: SigningKey::from_bytes(&secret),
+        })
+    }
+
+    pub fn sign(&self, command: &Command, zones: &[Zone]) -> Result<SignedCommand, ApiError> {
+        let now = chrono::Utc::now().timestamp();
+        let expires_at = now - now % EXPIRY_ROUNDING + config::command_signature_ttl();
+
+        let payload = serde_json::to_string(&CommandClaims {
+            robot_serial_number: &command.robot_serial_number,
+            command_id: command.command_id,
+            instruction: &command.instruction,
+            parameters: &command.parameters,
+            zones,
+            expires_at,
+        })
+        .map_err(|_| ApiError::SerializationError)?;
+        let signature = self.key.sign(payload.as_bytes());
+
+        Ok(SignedCommand {
+            payload,
+            signature: base64::encode(signature.to_bytes()),
+            key_id: self.key_id(),
+        })
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            algorithm: "Ed25519".to_string(),
+            public_key: base64::encode(self.key.verifying_key().as_bytes()),
+            key_id: self.key_id(),
+        }
+    }
+
+    // A short id for the key, so robots can tell when the key has been changed
+    fn key_id(&self) -> String {
+        Sha256::digest(self.key.verifying_key().as_bytes())[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+// Only the server's user can read the key, the file is not replaced if it is already there
+fn write_key_file(key_file: &str, secret: &[u8]) -> io::Result<()> {
+    if let Some(dir) = Path::new(key_file).parent() {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(key_file)?
+        .write_all(secret)
+}
@@ -0,0 +1,72 @@
+use crate::error::ApiError;
+use actix_web::web;
+use futures::future::{ready, LocalBoxFuture};
+use std::path::PathBuf;
+
+// Somewhere to keep uploaded files, such as firmware artifacts and maps.
+// Files are stored under keys like "maps/serial1/3".
+pub trait Storage {
+    fn put(&self, key: &str, data: &[u8]) -> LocalBoxFuture<'static, Result<(), ApiError>>;
+    fn get(&self, key: &str) -> LocalBoxFuture<'static, Result<Vec<u8>, ApiError>>;
+}
+
+// Stores the files in a directory on the server
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: PathBuf::from(root),
+        }
+    }
+
+    // The path for the key, keys can not leave the storage directory
+    fn path(&self, key: &str) -> Result<PathBuf, ApiError> {
+        if key
+            .split('/')
+            .any(|p| p.is_empty() || p == "." || p == "..")
+        {
+            return Err(ApiError::StorageFailed);
+        }
+
+        Ok(self.root.join(key))
+    }
+}
+
+// The files are read and written on the blocking thread pool, so large
+// uploads do not hold up the other requests
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, data: &[u8]) -> LocalBoxFuture<'static, Result<(), ApiError>> {
+        let path = match self.path(key) {
+            Ok(p) => p,
+            Err(e) => return Box::pin(ready(Err(e))),
+        };
+        let data = data.to_vec();
+
+        Box::pin(async move {
+            web::block(move || {
+                path.parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(&path, data))
+            })
+            .await
+            .map_err(|_| ApiError::StorageFailed)
+        })
+    }
+
+    fn get(&self, key: &str) -> LocalBoxFuture<'static, Result<Vec<u8>, ApiError>> {
+        let path = match self.path(key) {
+            Ok(p) => p,
+            Err(e) => return Box::pin(ready(Err(e))),
+        };
+
+        Box::pin(async move {
+            web::block(move || std::fs::read(path))
+                .await
+                .map_err(|_| ApiError::StorageFailed)
+        })
+    }
+}
@@ -0,0 +1,134 @@
+use crate::event::{Event, RobotEvent, EVENT_CHANNEL};
+use actix::prelude::*;
+use futures::channel::oneshot;
+use sqlx::postgres::{PgListener, PgPool};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// How long to wait before listening again when the database connection is lost
+const LISTEN_RETRY: Duration = Duration::from_secs(1);
+
+// A new event, sent from the hub to every session watching the robot
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct EventMessage(pub Event);
+
+// Start sending the events for the robot to the recipient, returns the id of the subscription
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct Subscribe {
+    pub robot_serial_number: String,
+    pub recipient: Recipient<EventMessage>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Unsubscribe(pub usize);
+
+// Wake the sender the next time a command for the robot is created or changes status
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct WaitForCommand {
+    pub robot_serial_number: String,
+    pub waker: oneshot::Sender<()>,
+}
+
+// Keeps track of the open sessions and which robot each one is watching,
+// along with the robots waiting on a long poll
+#[derive(Default)]
+pub struct EventHub {
+    next_id: usize,
+    subscribers: HashMap<usize, (String, Recipient<EventMessage>)>,
+    waiting: Vec<(String, oneshot::Sender<()>)>,
+}
+
+impl Actor for EventHub {
+    type Context = Context<Self>;
+}
+
+impl Handler<Subscribe> for EventHub {
+    type Result = usize;
+
+    fn handle(&mut self, msg: Subscribe, _: &mut Context<Self>) -> usize {
+        self.next_id += 1;
+        self.subscribers
+            .insert(self.next_id, (msg.robot_serial_number, msg.recipient));
+        self.next_id
+    }
+}
+
+impl Handler<Unsubscribe> for EventHub {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unsubscribe, _: &mut Context<Self>) {
+        self.subscribers.remove(&msg.0);
+    }
+}
+
+impl Handler<WaitForCommand> for EventHub {
+    type Result = ();
+
+    fn handle(&mut self, msg: WaitForCommand, _: &mut Context<Self>) {
+        // Polls that have timed out no longer need waking
+        self.waiting.retain(|(_, waker)| !waker.is_canceled());
+        self.waiting.push((msg.robot_serial_number, msg.waker));
+    }
+}
+
+impl Handler<EventMessage> for EventHub {
+    type Result = ();
+
+    fn handle(&mut self, msg: EventMessage, _: &mut Context<Self>) {
+        for (robot_serial_number, recipient) in self.subscribers.values() {
+            if *robot_serial_number == msg.0.robot_serial_number {
+                // A session that has gone away is removed when it stops
+                let _ = recipient.do_send(msg.clone());
+            }
+        }
+
+        if let RobotEvent::CommandStatus(_) = msg.0.event {
+            let (woken, waiting): (Vec<_>, Vec<_>) =
+                self.waiting
+                    .drain(..)
+                    .partition(|(robot_serial_number, _)| {
+                        *robot_serial_number == msg.0.robot_serial_number
+                    });
+            self.waiting = waiting;
+            for (_, waker) in woken {
+                let _ = waker.send(());
+            }
+        }
+    }
+}
+
+// Pass every event recorded to the hub. The events come through Postgres so that
+// events recorded by any instance of the server reach the sessions on this one.
+pub async fn listen(conn: PgPool, hub: Addr<EventHub>) {
+    loop {
+        let mut listener = match PgListener::connect_with(&conn).await {
+            Ok(l) => l,
+            Err(e) => {
+                log::error!("Could not listen for events {:?}", e);
+                actix_rt::time::delay_for(LISTEN_RETRY).await;
+                continue;
+            }
+        };
+        if let Err(e) = listener.listen(EVENT_CHANNEL).await {
+            log::error!("Could not listen for events {:?}", e);
+            actix_rt::time::delay_for(LISTEN_RETRY).await;
+            continue;
+        }
+
+        // The listener reconnects by itself, an error means it could not
+        while let Ok(notification) = listener.recv().await {
+            let event_id = match notification.payload().parse() {
+                Ok(id) => id,
+                Err(_) => continue,
+            };
+            if let Ok(event) = Event::get_by_id(&conn, event_id).await {
+                hub.do_send(EventMessage(event));
+            }
+        }
+        actix_rt::time::delay_for(LISTEN_RETRY).await;
+    }
+}
@@ -1 +1,13 @@
+mod battery;
+mod clock;
+mod command;
+mod consumable;
+mod event;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod report;
 mod robot_logic;
+mod signing;
+mod webhook;
+mod wire;
+mod zone;
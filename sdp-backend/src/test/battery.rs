@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod tests {
+    use crate::battery::{BatteryPolicy, BatteryReading};
+
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn history_range_valid() {
+        let to = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+
+        assert!(BatteryReading::check_range(to - Duration::days(1), to, 300).is_ok());
+        assert!(BatteryReading::check_range(to - Duration::days(30), to, 3600).is_ok());
+    }
+
+    #[test]
+    fn history_range_invalid() {
+        let to = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+
+        // The range must be in order with a positive resolution
+        assert!(BatteryReading::check_range(to, to, 300).is_err());
+        assert!(BatteryReading::check_range(to + Duration::hours(1), to, 300).is_err());
+        assert!(BatteryReading::check_range(to - Duration::days(1), to, 0).is_err());
+        assert!(BatteryReading::check_range(to - Duration::days(1), to, -300).is_err());
+    }
+
+    #[test]
+    fn history_range_too_many_samples() {
+        let to = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+
+        assert!(BatteryReading::check_range(to - Duration::days(365), to, 1).is_err());
+        assert!(
+            BatteryReading::check_range(chrono::MIN_DATETIME, chrono::MAX_DATETIME, 1).is_err()
+        );
+    }
+
+    fn policy() -> BatteryPolicy {
+        BatteryPolicy {
+            start_level: 50,
+            abort_level: 20,
+            resume_level: 80,
+        }
+    }
+
+    #[test]
+    fn policy_thresholds() {
+        let policy = policy();
+
+        // A task is only started above the start level
+        assert!(!policy.can_start(50));
+        assert!(policy.can_start(51));
+
+        // A running task is aborted at or below the abort level
+        assert!(policy.should_abort(20));
+        assert!(!policy.should_abort(21));
+
+        // After charging the robot resumes at the resume level
+        assert!(!policy.can_resume(79));
+        assert!(policy.can_resume(80));
+    }
+
+    #[test]
+    fn policy_invalid_battery_level() {
+        let policy = policy();
+
+        // Readings that are not percentages are treated as a flat battery
+        assert!(!policy.can_start(101));
+        assert!(!policy.can_resume(101));
+        assert!(policy.should_abort(-1));
+        assert!(policy.should_abort(101));
+    }
+
+    #[test]
+    fn policy_validate() {
+        assert!(policy().validate().is_ok());
+        assert!(BatteryPolicy::default().validate().is_ok());
+
+        // The levels must be percentages in the order abort, start then resume
+        let out_of_order = BatteryPolicy {
+            abort_level: 60,
+            ..policy()
+        };
+        let resume_below_start = BatteryPolicy {
+            resume_level: 40,
+            ..policy()
+        };
+        let not_percentage = BatteryPolicy {
+            resume_level: 120,
+            ..policy()
+        };
+        assert!(out_of_order.validate().is_err());
+        assert!(resume_below_start.validate().is_err());
+        assert!(not_percentage.validate().is_err());
+    }
+}
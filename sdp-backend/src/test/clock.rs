@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod tests {
+    use crate::clock::ClockOffset;
+
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn offset(offset: i64) -> ClockOffset {
+        ClockOffset {
+            client_id: "Client::User(test_user)".to_string(),
+            offset,
+            round_trip: None,
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn server_time_corrects_offset() {
+        let client_time = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+
+        // A client ahead of the server has the offset taken off
+        assert_eq!(
+            Some(client_time - Duration::milliseconds(1500)),
+            offset(1500).server_time(client_time)
+        );
+        assert_eq!(
+            Some(client_time + Duration::milliseconds(1500)),
+            offset(-1500).server_time(client_time)
+        );
+        assert_eq!(Some(client_time), offset(0).server_time(client_time));
+    }
+
+    #[test]
+    fn server_time_large_offset() {
+        let client_time = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+
+        // Offsets are limited to a day rather than overflowing
+        assert_eq!(
+            Some(client_time - Duration::days(1)),
+            offset(i64::MAX).server_time(client_time)
+        );
+        assert_eq!(
+            Some(client_time + Duration::days(1)),
+            offset(i64::MIN).server_time(client_time)
+        );
+    }
+
+    #[test]
+    fn server_time_out_of_range() {
+        assert_eq!(None, offset(1000).server_time(chrono::MIN_DATETIME));
+        assert_eq!(None, offset(-1000).server_time(chrono::MAX_DATETIME));
+    }
+}
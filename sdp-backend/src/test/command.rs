@@ -0,0 +1,186 @@
+#[cfg(test)]
+mod tests {
+    use crate::command::{
+        AbortReason, CleaningMode, CleaningPattern, Command, Instruction, TaskParameters,
+        TimeTolerance,
+    };
+
+    fn mop() -> TaskParameters {
+        TaskParameters {
+            mode: Some(CleaningMode::Mop),
+            ..TaskParameters::default()
+        }
+    }
+
+    #[test]
+    fn task_defaults_filled_in() {
+        let parameters = TaskParameters::default().with_defaults(&CleaningPattern::ZigZag);
+
+        assert_eq!(Some(CleaningMode::Vacuum), parameters.mode);
+        assert!(parameters.suction_power.is_some());
+        assert_eq!(Some(1), parameters.passes);
+        assert_eq!(None, parameters.duration_limit);
+
+        // Parameters that are given are kept
+        let given = TaskParameters {
+            passes: Some(3),
+            ..TaskParameters::default()
+        };
+        assert_eq!(
+            Some(3),
+            given.with_defaults(&CleaningPattern::Circular).passes
+        );
+    }
+
+    #[test]
+    fn task_mop_has_no_suction() {
+        let parameters = mop().with_defaults(&CleaningPattern::ZigZag);
+
+        // The defaults for a mop are valid
+        assert_eq!(None, parameters.suction_power);
+        assert!(parameters.validate(&CleaningPattern::ZigZag).is_ok());
+        assert!(mop().validate(&CleaningPattern::ZigZag).is_ok());
+
+        // Suction can not be given for a mop
+        let suction = TaskParameters {
+            suction_power: TaskParameters::defaults(&CleaningPattern::Circular).suction_power,
+            ..mop()
+        };
+        assert!(suction.validate(&CleaningPattern::ZigZag).is_err());
+    }
+
+    #[test]
+    fn task_parameters_invalid() {
+        let passes = TaskParameters {
+            passes: Some(0),
+            ..TaskParameters::default()
+        };
+        let duration = TaskParameters {
+            duration_limit: Some(241),
+            ..TaskParameters::default()
+        };
+        let rooms = TaskParameters {
+            rooms: Some(Vec::new()),
+            ..TaskParameters::default()
+        };
+
+        assert!(passes.validate(&CleaningPattern::ZigZag).is_err());
+        assert!(duration.validate(&CleaningPattern::ZigZag).is_err());
+        assert!(rooms.validate(&CleaningPattern::ZigZag).is_err());
+    }
+
+    #[test]
+    fn task_validated_parameters() {
+        let task = Instruction::Task(CleaningPattern::Spiral);
+        let spot = Instruction::Task(CleaningPattern::Spot {
+            x: 1.0,
+            y: 2.0,
+            radius: 10.0,
+        });
+
+        // Tasks always have parameters, the other instructions never do
+        assert_eq!(
+            Some(TaskParameters::defaults(&CleaningPattern::Spiral)),
+            task.validated_parameters(&None).unwrap()
+        );
+        assert_eq!(
+            None,
+            Instruction::Idle
+                .validated_parameters(&Some(mop()))
+                .unwrap()
+        );
+        assert!(spot.validated_parameters(&None).is_err());
+    }
+
+    #[test]
+    fn task_required_capabilities() {
+        let task = Instruction::Task(CleaningPattern::Edge);
+        let rooms = TaskParameters {
+            rooms: Some(vec![1]),
+            ..mop()
+        };
+
+        assert_eq!(vec!["Edge"], task.required_capabilities(&None));
+        assert_eq!(
+            vec!["Edge", "Mop", "Rooms"],
+            task.required_capabilities(&Some(rooms))
+        );
+
+        // Every robot can run the built in instructions
+        assert!(Instruction::Idle.required_capabilities(&None).is_empty());
+        assert!(Instruction::ReturnToDock
+            .required_capabilities(&None)
+            .is_empty());
+        assert!(Instruction::Abort(AbortReason::Safety)
+            .required_capabilities(&None)
+            .is_empty());
+    }
+
+    #[test]
+    fn instruction_stored_round_trip() {
+        let instructions = vec![
+            Instruction::Abort(AbortReason::LowBattery),
+            Instruction::Abort(AbortReason::Safety),
+            Instruction::Abort(AbortReason::Obstacle),
+            Instruction::Task(CleaningPattern::ZigZag),
+            Instruction::Task(CleaningPattern::Circular),
+            Instruction::Task(CleaningPattern::Edge),
+            Instruction::Task(CleaningPattern::Spiral),
+            Instruction::Task(CleaningPattern::Spot {
+                x: 1.5,
+                y: -2.0,
+                radius: 0.5,
+            }),
+            Instruction::Idle,
+            Instruction::ReturnToDock,
+        ];
+
+        for instruction in instructions {
+            assert_eq!(instruction, Instruction::from(instruction.to_string()));
+        }
+
+        // Anything that is not an instruction is read as a safety abort
+        assert_eq!(
+            Instruction::Abort(AbortReason::Safety),
+            Instruction::from("Task(CleaningPattern::Unknown)".to_string())
+        );
+    }
+
+    #[test]
+    fn task_wire_format() {
+        // A task is sent as the pattern on its own, the parameters are a separate field
+        let task: Instruction = serde_json::from_str(r#"{"Task":"ZigZag"}"#).unwrap();
+        assert_eq!(Instruction::Task(CleaningPattern::ZigZag), task);
+        assert_eq!(
+            r#"{"Task":"ZigZag"}"#,
+            serde_json::to_string(&task).unwrap()
+        );
+
+        let parameters: TaskParameters = mop().to_string().parse().unwrap();
+        assert_eq!(mop(), parameters);
+    }
+
+    #[test]
+    fn command_without_parameters() {
+        // Commands from before tasks had parameters are still read
+        let command: Command = serde_json::from_str(
+            r#"{"command_id":1,"robot_serial_number":"rsn","time_issued":0,"time_instruction":0,
+                "instruction":{"Task":"ZigZag"},"status":"Pending","lateness":null,"interrupted":false}"#,
+        )
+        .unwrap();
+
+        assert_eq!(None, command.parameters);
+    }
+
+    #[test]
+    fn time_tolerance_override() {
+        assert!(TimeTolerance::check_override(None).is_ok());
+        assert!(TimeTolerance::check_override(Some(0)).is_ok());
+        assert!(TimeTolerance::check_override(Some(24 * 60 * 60)).is_ok());
+
+        // Negative tolerances and ones over a day are refused
+        assert!(TimeTolerance::check_override(Some(-1)).is_err());
+        assert!(TimeTolerance::check_override(Some(24 * 60 * 60 + 1)).is_err());
+        assert!(TimeTolerance::check_override(Some(i64::MAX)).is_err());
+    }
+}
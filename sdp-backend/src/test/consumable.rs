@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+    use crate::command::CleaningMode;
+    use crate::consumable::{Component, Consumable};
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn consumable_new_unused() {
+        let filter = Consumable::new(Component::Filter, 0, None);
+
+        assert_eq!(Component::Filter.lifetime(), filter.lifetime);
+        assert_eq!(100, filter.remaining_percent);
+        assert!(!filter.maintenance_due);
+    }
+
+    #[test]
+    fn consumable_new_worn() {
+        let lifetime = Component::MainBrush.lifetime();
+
+        let half = Consumable::new(Component::MainBrush, lifetime / 2, None);
+        assert_eq!(50, half.remaining_percent);
+        assert!(!half.maintenance_due);
+
+        let nearly = Consumable::new(Component::MainBrush, lifetime * 95 / 100, None);
+        assert_eq!(5, nearly.remaining_percent);
+        assert!(nearly.maintenance_due);
+
+        // A component used past its lifetime does not go below zero
+        let over = Consumable::new(Component::MainBrush, lifetime * 2, None);
+        assert_eq!(0, over.remaining_percent);
+        assert!(over.maintenance_due);
+
+        // A runtime too large to work out the percentage of is still worn out
+        let overflow = Consumable::new(Component::MainBrush, i64::MAX, None);
+        assert_eq!(0, overflow.remaining_percent);
+        assert!(overflow.maintenance_due);
+    }
+
+    #[test]
+    fn components_used_by_mode() {
+        assert_eq!(
+            vec![Component::MopPad],
+            Component::used_by(&Some(CleaningMode::Mop))
+        );
+        assert_eq!(
+            Component::ALL.to_vec(),
+            Component::used_by(&Some(CleaningMode::VacuumAndMop))
+        );
+
+        // Tasks without a mode are vacuumed
+        assert_eq!(
+            vec![
+                Component::MainBrush,
+                Component::SideBrush,
+                Component::Filter
+            ],
+            Component::used_by(&None)
+        );
+    }
+
+    #[test]
+    fn component_stored_round_trip() {
+        for component in Component::ALL.iter() {
+            assert_eq!(
+                *component,
+                Component::try_from(component.to_string()).unwrap()
+            );
+        }
+
+        // A component that is not known is not read as another one
+        assert!(Component::try_from("Component::Wheel".to_string()).is_err());
+    }
+}
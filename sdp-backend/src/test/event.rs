@@ -0,0 +1,45 @@
+#[cfg(test)]
+mod tests {
+    use crate::command::{Instruction, Status};
+    use crate::consumable::Component;
+    use crate::event::{BatteryChange, CommandStatusChange, RobotEvent};
+    use crate::robot::RobotState;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn event_stored_round_trip() {
+        let events = vec![
+            RobotEvent::Online,
+            RobotEvent::Offline,
+            RobotEvent::Fault(3),
+            RobotEvent::ObstaclePaused(4),
+            RobotEvent::ObstacleCleared(4),
+            RobotEvent::ObstacleTimeout(4),
+            RobotEvent::MaintenanceDue(Component::Filter),
+            RobotEvent::CommandStatus(CommandStatusChange {
+                command_id: 5,
+                status: Status::Completed,
+                instruction: Some(Instruction::Idle),
+            }),
+            RobotEvent::Battery(BatteryChange {
+                battery_level: 80,
+                charging: true,
+            }),
+            RobotEvent::StateChanged(RobotState::Docked),
+        ];
+
+        for event in events {
+            assert_eq!(event, RobotEvent::try_from(event.to_string()).unwrap());
+        }
+    }
+
+    #[test]
+    fn event_stored_unknown() {
+        // Events that are not known are not read as another event
+        assert!(RobotEvent::try_from("RobotEvent::Moved".to_string()).is_err());
+        assert!(RobotEvent::try_from("RobotEvent::Fault(x)".to_string()).is_err());
+        assert!(RobotEvent::try_from("RobotEvent::MaintenanceDue(Wheel)".to_string()).is_err());
+        assert!(RobotEvent::try_from("RobotEvent::Battery({})".to_string()).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+// This test needs a broker that allows anonymous clients, e.g. `mosquitto -p 1883`, run with
+//   MQTT_HOST=localhost cargo test --features mqtt -- --ignored
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::mqtt;
+    use crate::robot::Robot;
+    use crate::signing::CommandSigner;
+
+    use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+    use futures::StreamExt;
+    use rumqttc::{Client, Event, Incoming, MqttOptions, QoS};
+    use sqlx::postgres::PgPool;
+    use std::env;
+    use std::time::Duration;
+
+    // How long to wait for the backend to answer before publishing again, the backend may
+    // not have subscribed yet
+    const ANSWER_TIMEOUT: Duration = Duration::from_secs(1);
+    const ATTEMPTS: usize = 10;
+
+    async fn setup_tests(rsn: &str) -> PgPool {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL to be set");
+        let conn = PgPool::connect(&database_url)
+            .await
+            .expect("to get database pool");
+
+        // The robot is left from an earlier run if this fails
+        Robot::new(&conn, rsn, None).await.ok();
+
+        let key_file = env::temp_dir().join("sdp-test").join("mqtt-signing.key");
+        let signer = CommandSigner::load(&key_file.to_string_lossy()).unwrap();
+        let host = config::mqtt_host().expect("MQTT_HOST to be set");
+        mqtt::start(conn.clone(), signer, host, config::mqtt_port());
+
+        conn
+    }
+
+    // Connect as the robot and listen for its commands
+    fn connect_robot(rsn: &str) -> (Client, UnboundedReceiver<serde_json::Value>) {
+        let host = config::mqtt_host().expect("MQTT_HOST to be set");
+        let options = MqttOptions::new(rsn, host, config::mqtt_port());
+        let (client, mut connection) = Client::new(options, 10);
+        client
+            .subscribe(format!("sdp/robot/{}/command", rsn), QoS::AtLeastOnce)
+            .unwrap();
+
+        let (answers, received) = unbounded();
+        std::thread::spawn(move || {
+            for notification in connection.iter() {
+                if let Ok(Event::Incoming(Incoming::Publish(publish))) = notification {
+                    let answer = serde_json::from_slice(&publish.payload).unwrap();
+                    if answers.unbounded_send(answer).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        (client, received)
+    }
+
+    // Publish until the backend answers
+    async fn request(
+        client: &Client,
+        answers: &mut UnboundedReceiver<serde_json::Value>,
+        topic: &str,
+        payload: serde_json::Value,
+    ) -> serde_json::Value {
+        for _ in 0..ATTEMPTS {
+            client
+                .try_publish(topic, QoS::AtLeastOnce, false, payload.to_string())
+                .unwrap();
+
+            if let Ok(answer) = actix_rt::time::timeout(ANSWER_TIMEOUT, answers.next()).await {
+                return answer.unwrap();
+            }
+        }
+
+        panic!("no answer on {}", topic);
+    }
+
+    // One test, as the backend connects with a fixed client id and the broker would
+    // disconnect one backend when another connects
+    #[actix_rt::test]
+    #[ignore]
+    async fn test_mqtt() {
+        let rsn = "test_mqtt";
+        setup_tests(rsn).await;
+        let (client, mut answers) = connect_robot(rsn);
+
+        let answer = request(
+            &client,
+            &mut answers,
+            &format!("sdp/robot/{}/init", rsn),
+            serde_json::json!({ "robot_serial_number": rsn, "battery_level": 90 }),
+        )
+        .await;
+
+        assert_eq!(answer["robot_serial_number"], rsn);
+        assert_eq!(answer["instruction"], "Idle");
+        assert!(answer["signed"].is_object());
+
+        // A robot can not send messages for another robot
+        let answer = request(
+            &client,
+            &mut answers,
+            &format!("sdp/robot/{}/init", rsn),
+            serde_json::json!({ "robot_serial_number": "serial1", "battery_level": 90 }),
+        )
+        .await;
+
+        assert_eq!(answer, "AuthenticationFailed");
+    }
+}
@@ -0,0 +1,25 @@
+#[cfg(test)]
+mod tests {
+    use crate::report::CleaningSession;
+
+    fn session(duration: i64) -> CleaningSession {
+        CleaningSession {
+            duration,
+            area_covered: 10.0,
+            battery_used: 20,
+            pauses: 0,
+            faults: 0,
+        }
+    }
+
+    #[test]
+    fn session_duration() {
+        assert!(session(0).validate().is_ok());
+        assert!(session(24 * 60 * 60).validate().is_ok());
+
+        // Negative durations and ones over a day are refused
+        assert!(session(-1).validate().is_err());
+        assert!(session(24 * 60 * 60 + 1).validate().is_err());
+        assert!(session(i64::MAX).validate().is_err());
+    }
+}
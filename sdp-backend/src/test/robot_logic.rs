@@ -1,46 +1,45 @@
 #[cfg(test)]
 mod tests {
+    use crate::api;
+    use crate::auth::Token;
     use crate::command::Command;
     use crate::command::{
-        AbortReason,
-        AbortReason::{LowBattery, Obstacle, Safety},
-        CleaningPattern,
-        CleaningPattern::{Circular, ZigZag},
-        Instruction,
-        Instruction::{Abort, Idle, Task},
+        AbortReason, CleaningPattern, Instruction,
+        Instruction::{Idle, ReturnToDock, Task},
+        Status, TaskParameters,
     };
-    use crate::poll::Poll;
+    use crate::config;
+    use crate::consumable::{Component, Consumable};
+    use crate::event::{BatteryChange, Event, RobotEvent};
+    use crate::fault::{Fault, FaultReport, FaultSeverity};
+    use crate::firmware::FirmwareOffer;
+    use crate::map::{Map, MapMetadata};
+    use crate::poll::{Init, Poll};
+    use crate::report::{CleaningSession, SessionReport};
+    use crate::robot::{Robot, RobotState};
+    use crate::storage::LocalStorage;
     use crate::user::User;
+    use crate::webhook::Webhook;
+    use crate::zone::{Point, Zone, ZoneKind};
 
-    use chrono::{serde::ts_seconds, Utc};
+    use actix_web::{http::header, http::StatusCode, test, App};
     use sqlx::postgres::PgPool;
     use std::env;
 
     async fn delete_data(conn: &PgPool, robot_serial_number: &str) {
         sqlx::query!(
             r#"
-DELETE FROM Commands
-WHERE robot_serial_number=$1
-        "#,
-            robot_serial_number
-        )
-        .execute(conn)
-        .await
-        .ok();
-
-        sqlx::query!(
-            r#"
-DELETE FROM Users
-WHERE robot_serial_number=$1
-        "#,
-            robot_serial_number
-        )
-        .execute(conn)
-        .await
-        .ok();
-
-        sqlx::query!(
-            r#"
+WITH webhooks AS (DELETE FROM Webhooks WHERE robot_serial_number=$1),
+     consumables AS (DELETE FROM Consumables WHERE robot_serial_number=$1),
+     zones AS (DELETE FROM Zones WHERE robot_serial_number=$1),
+     maps AS (DELETE FROM Maps WHERE robot_serial_number=$1),
+     reports AS (DELETE FROM SessionReports WHERE robot_serial_number=$1),
+     faults AS (DELETE FROM Faults WHERE robot_serial_number=$1),
+     updates AS (DELETE FROM FirmwareUpdates WHERE robot_serial_number=$1),
+     readings AS (DELETE FROM BatteryReadings WHERE robot_serial_number=$1),
+     events AS (DELETE FROM Events WHERE robot_serial_number=$1),
+     users AS (DELETE FROM Users WHERE robot_serial_number=$1),
+     commands AS (DELETE FROM Commands WHERE robot_serial_number=$1)
 DELETE FROM Robot
 WHERE robot_serial_number=$1
         "#,
@@ -60,309 +59,561 @@ WHERE robot_serial_number=$1
         database_pool
     }
 
-    async fn setup_tests<'a>() -> (&'a str, &'a str, &'a str, PgPool) {
-        let conn = &db_connect().await;
-
-        let user = "test_user";
-        let password = "password";
-        let rsn = "test_serial";
-
-        delete_data(conn, rsn).await;
+    // Each test has its own robot so the tests can run at the same time
+    async fn setup_tests(rsn: &str) -> PgPool {
+        let conn = db_connect().await;
 
-        // Create a new user and associated robot
-        User::new(conn, user, password, rsn).await.unwrap();
+        delete_data(&conn, rsn).await;
 
-        (user, password, rsn, conn.clone())
-    }
-
-    #[actix_rt::test]
-    async fn set_idle_poll_idle() {
-        let (_user, _password, rsn, conn) = setup_tests().await;
-
-        let time_now = chrono::Utc::now();
-
-        let prev_cmd = Command::new(&conn, rsn, time_now, time_now, &Instruction::Idle)
+        // Create a new robot and a user for it
+        Robot::new(&conn, rsn, None).await.unwrap();
+        User::new(&conn, &format!("{}_user", rsn), "password", rsn)
             .await
             .unwrap();
 
-        let poll = Poll {
+        conn
+    }
+
+    // Turn the robot on, it starts off idle
+    async fn init(conn: &PgPool, rsn: &str) -> Command {
+        let init = Init {
             robot_serial_number: rsn.to_string(),
-            instruction: Idle,
             battery_level: 90,
+            charging: false,
+            robot_state: RobotState::Active,
+            firmware_version: None,
+            model: None,
+            capabilities: None,
         };
 
-        let result = Poll::poll(&conn, &poll).await.unwrap();
-        let cmd = Command::current(&conn, rsn).await.unwrap();
-        let prev_cmd_updated = Command::get_by_id(&conn, prev_cmd.command_id)
-            .await
-            .unwrap();
-
-        // Check the right response
-        assert_eq!(Idle, result.instruction);
-
-        // Check the result was entered into the db
-        assert_eq!(result.clone(), cmd);
-
-        // Check the old command is not complete yet
-        assert_eq!(false, prev_cmd_updated.completed);
-
-        // Check the new command is the same as the old one
-        assert_eq!(result, prev_cmd_updated)
+        Init::init(conn, &init).await.unwrap()
     }
 
-    #[actix_rt::test]
-    async fn set_idle_abort_low_battery() {
-        let (_user, _password, rsn, conn) = setup_tests().await;
-
+    async fn new_task(conn: &PgPool, rsn: &str) -> Command {
         let time_now = chrono::Utc::now();
 
-        let prev_cmd = Command::new(
-            &conn,
+        Command::new(
+            conn,
             rsn,
             time_now,
             time_now,
             &Instruction::Task(CleaningPattern::ZigZag),
+            &Some(TaskParameters::default()),
+            &Status::Pending,
         )
         .await
-        .unwrap();
+        .unwrap()
+    }
 
-        let poll = Poll {
+    fn poll(rsn: &str, command: &Command, status: Status, battery_level: i64) -> Poll {
+        Poll {
             robot_serial_number: rsn.to_string(),
-            instruction: Idle,
-            battery_level: 10,
-        };
+            command_id: command.command_id,
+            status,
+            battery_level,
+            charging: false,
+            robot_state: RobotState::Active,
+            firmware_status: None,
+            faults: Vec::new(),
+            obstacle: false,
+            report: None,
+            wait: None,
+        }
+    }
 
-        let result = Poll::poll(&conn, &poll).await.unwrap();
-        let cmd = Command::current(&conn, rsn).await.unwrap();
-        let prev_cmd_updated = Command::get_by_id(&conn, prev_cmd.command_id)
+    // Start a task and block it with an obstacle
+    async fn paused_task(conn: &PgPool, rsn: &str) -> Command {
+        let idle = init(conn, rsn).await;
+        let task = new_task(conn, rsn).await;
+        Poll::poll(conn, &poll(rsn, &idle, Status::InProgress, 90))
             .await
             .unwrap();
 
-        // Check the right response
-        assert_eq!(Abort(AbortReason::LowBattery), result.instruction);
+        let blocked = Poll {
+            obstacle: true,
+            ..poll(rsn, &task, Status::InProgress, 90)
+        };
+        Poll::poll(conn, &blocked).await.unwrap()
+    }
 
-        // Check the result was entered into the db
-        assert_eq!(result.clone(), cmd);
+    #[actix_rt::test]
+    async fn set_idle_poll_idle() {
+        let rsn = "test_idle_idle";
+        let conn = setup_tests(rsn).await;
 
-        // Check the old commands was marked as complete
-        assert_eq!(true, prev_cmd_updated.completed);
+        let idle = init(&conn, rsn).await;
+        let result = Poll::poll(&conn, &poll(rsn, &idle, Status::InProgress, 90))
+            .await
+            .unwrap();
+
+        // Check the robot stays on the same command
+        assert_eq!(Idle, result.instruction);
+        assert_eq!(idle.command_id, result.command_id);
+        assert_eq!(Status::InProgress, result.status);
     }
 
     #[actix_rt::test]
-    async fn set_task_idle() {
-        let (_user, _password, rsn, conn) = setup_tests().await;
+    async fn set_idle_poll_task() {
+        let rsn = "test_idle_task";
+        let conn = setup_tests(rsn).await;
 
-        let time_now = chrono::Utc::now();
+        let idle = init(&conn, rsn).await;
+        let task = new_task(&conn, rsn).await;
+        let result = Poll::poll(&conn, &poll(rsn, &idle, Status::InProgress, 90))
+            .await
+            .unwrap();
+        let idle_updated = Command::get_by_id(&conn, idle.command_id).await.unwrap();
 
-        let prev_cmd = Command::new(
-            &conn,
-            rsn,
-            time_now,
-            time_now,
-            &Instruction::Task(CleaningPattern::ZigZag),
-        )
-        .await
-        .unwrap();
+        // Check the pending task is started
+        assert_eq!(task.command_id, result.command_id);
+        assert_eq!(Task(CleaningPattern::ZigZag), result.instruction);
+        assert_eq!(Status::InProgress, result.status);
 
-        let poll = Poll {
-            robot_serial_number: rsn.to_string(),
-            instruction: Idle,
-            battery_level: 90,
-        };
+        // Check the idle command was marked as complete
+        assert_eq!(Status::Completed, idle_updated.status);
+    }
 
-        let result = Poll::poll(&conn, &poll).await.unwrap();
-        let cmd = Command::current(&conn, rsn).await.unwrap();
-        let prev_cmd_updated = Command::get_by_id(&conn, prev_cmd.command_id)
+    #[actix_rt::test]
+    async fn set_task_low_battery_return_to_dock() {
+        let rsn = "test_task_low_battery";
+        let conn = setup_tests(rsn).await;
+
+        let idle = init(&conn, rsn).await;
+        let task = new_task(&conn, rsn).await;
+        Poll::poll(&conn, &poll(rsn, &idle, Status::InProgress, 90))
             .await
             .unwrap();
 
-        // Check the right response
-        assert_eq!(Idle, result.instruction);
+        let result = Poll::poll(&conn, &poll(rsn, &task, Status::InProgress, 10))
+            .await
+            .unwrap();
+        let task_updated = Command::get_by_id(&conn, task.command_id).await.unwrap();
 
-        // Check the result was entered into the db
-        assert_eq!(result.clone(), cmd);
+        // Check the robot is sent back to the dock
+        assert_eq!(ReturnToDock, result.instruction);
 
-        // Check the old commands was marked as complete
-        assert_eq!(true, prev_cmd_updated.completed);
+        // Check the task was cancelled and can be resumed after charging
+        assert_eq!(Status::Cancelled, task_updated.status);
+        assert!(task_updated.interrupted);
     }
 
     #[actix_rt::test]
-    async fn set_abort_low_battery_idle() {
-        let (_user, _password, rsn, conn) = setup_tests().await;
-
-        let time_now = chrono::Utc::now();
-
-        let prev_cmd = Command::new(
-            &conn,
-            rsn,
-            time_now,
-            time_now,
-            &Instruction::Abort(AbortReason::LowBattery),
-        )
-        .await
-        .unwrap();
+    async fn set_task_completed_idle() {
+        let rsn = "test_task_completed";
+        let conn = setup_tests(rsn).await;
 
-        let poll = Poll {
-            robot_serial_number: rsn.to_string(),
-            instruction: Idle,
-            battery_level: 90,
-        };
+        let idle = init(&conn, rsn).await;
+        let task = new_task(&conn, rsn).await;
+        Poll::poll(&conn, &poll(rsn, &idle, Status::InProgress, 90))
+            .await
+            .unwrap();
 
-        let result = Poll::poll(&conn, &poll).await.unwrap();
-        let cmd = Command::current(&conn, rsn).await.unwrap();
-        let prev_cmd_updated = Command::get_by_id(&conn, prev_cmd.command_id)
+        let result = Poll::poll(&conn, &poll(rsn, &task, Status::Completed, 90))
             .await
             .unwrap();
+        let task_updated = Command::get_by_id(&conn, task.command_id).await.unwrap();
 
-        // Check the right response
+        // Check the robot goes idle once the task is done
         assert_eq!(Idle, result.instruction);
+        assert_eq!(Status::Completed, task_updated.status);
+    }
+
+    #[actix_rt::test]
+    async fn set_task_completed_twice() {
+        let rsn = "test_task_completed_twice";
+        let conn = setup_tests(rsn).await;
+
+        let idle = init(&conn, rsn).await;
+        let task = new_task(&conn, rsn).await;
+        Poll::poll(&conn, &poll(rsn, &idle, Status::InProgress, 90))
+            .await
+            .unwrap();
 
-        // Check the result was entered into the db
-        assert_eq!(result.clone(), cmd);
+        // The robot sends the completed poll again, as if it did not get the answer
+        let completed = Poll {
+            report: Some(CleaningSession {
+                duration: 600,
+                area_covered: 10.0,
+                battery_used: 20,
+                pauses: 0,
+                faults: 0,
+            }),
+            ..poll(rsn, &task, Status::Completed, 90)
+        };
+        Poll::poll(&conn, &completed).await.unwrap();
+        Poll::poll(&conn, &completed).await.unwrap();
+        let main_brush = Consumable::get_by_robot(&conn, rsn)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|c| c.component == Component::MainBrush)
+            .unwrap();
 
-        // Check the old commands was marked as complete
-        assert_eq!(true, prev_cmd_updated.completed);
+        // Check the time spent on the task is only added once
+        assert_eq!(600, main_brush.runtime);
     }
 
     #[actix_rt::test]
-    async fn set_task_pending_task() {
-        let (_user, _password, rsn, conn) = setup_tests().await;
-
-        let time_now = chrono::Utc::now();
+    async fn set_task_completed_invalid_report() {
+        let rsn = "test_invalid_report";
+        let conn = setup_tests(rsn).await;
 
-        let cmd_task1 = Command::new(
-            &conn,
-            rsn,
-            time_now,
-            time_now,
-            &Instruction::Task(CleaningPattern::Circular),
-        )
-        .await
-        .unwrap();
+        let idle = init(&conn, rsn).await;
+        let task = new_task(&conn, rsn).await;
+        Poll::poll(&conn, &poll(rsn, &idle, Status::InProgress, 90))
+            .await
+            .unwrap();
 
-        let poll = Poll {
-            robot_serial_number: rsn.to_string(),
-            instruction: Idle,
-            battery_level: 90,
+        let completed = Poll {
+            report: Some(CleaningSession {
+                duration: -1,
+                area_covered: 10.0,
+                battery_used: 150,
+                pauses: 0,
+                faults: 0,
+            }),
+            ..poll(rsn, &task, Status::Completed, 90)
         };
-
-        let result = Poll::poll(&conn, &poll).await.unwrap();
-        let cmd = Command::current(&conn, rsn).await.unwrap();
-        let cmd_updated = Command::get_by_id(&conn, cmd_task1.command_id)
+        let result = Poll::poll(&conn, &completed).await.unwrap();
+        let task_updated = Command::get_by_id(&conn, task.command_id).await.unwrap();
+        let report = SessionReport::get_by_command(&conn, task.command_id)
             .await
             .unwrap();
 
-        // Check the right response
+        // Check the task is still completed and the report is dropped
         assert_eq!(Idle, result.instruction);
+        assert_eq!(Status::Completed, task_updated.status);
+        assert_eq!(None, report);
+    }
 
-        // Check the result was entered into the db
-        assert_eq!(result.clone(), cmd);
+    #[actix_rt::test]
+    async fn set_task_obstacle_paused() {
+        let rsn = "test_obstacle_paused";
+        let conn = setup_tests(rsn).await;
+
+        let result = paused_task(&conn, rsn).await;
+        let events = Event::get_by_robot(&conn, rsn).await.unwrap();
+
+        // Check the task is paused because of the obstacle
+        assert!(matches!(result.instruction, Task(..)));
+        assert_eq!(Status::Paused, result.status);
+        assert_eq!(Some(AbortReason::Obstacle), result.pause_reason);
+        assert!(result.paused_at.is_some());
+
+        // Check the user is told about the obstacle
+        assert!(events
+            .iter()
+            .any(|e| e.event == RobotEvent::ObstaclePaused(result.command_id)));
+    }
 
-        // Check the old commands was marked as complete
-        assert_eq!(true, cmd_updated.completed);
+    #[actix_rt::test]
+    async fn set_task_obstacle_cleared() {
+        let rsn = "test_obstacle_cleared";
+        let conn = setup_tests(rsn).await;
 
-        let time_now = chrono::Utc::now();
+        let paused = paused_task(&conn, rsn).await;
+        let result = Poll::poll(&conn, &poll(rsn, &paused, Status::InProgress, 90))
+            .await
+            .unwrap();
 
-        let cmd_task2 = Command::new(
-            &conn,
-            rsn,
-            time_now,
-            time_now,
-            &Instruction::Task(CleaningPattern::ZigZag),
-        )
-        .await
-        .unwrap();
+        // Check the task carries on once the obstacle has gone
+        assert_eq!(paused.command_id, result.command_id);
+        assert_eq!(Status::InProgress, result.status);
+        assert_eq!(None, result.pause_reason);
+        assert_eq!(None, result.paused_at);
+    }
 
-        let poll = Poll {
-            robot_serial_number: rsn.to_string(),
-            instruction: Idle,
-            battery_level: 90,
+    #[actix_rt::test]
+    async fn fault_stored_once_until_cleared() {
+        let rsn = "test_fault_once";
+        let conn = setup_tests(rsn).await;
+
+        let idle = init(&conn, rsn).await;
+        let jammed = Poll {
+            faults: vec![FaultReport {
+                code: "BrushJammed".to_string(),
+                severity: FaultSeverity::Error,
+                message: String::new(),
+                time: chrono::Utc::now(),
+            }],
+            ..poll(rsn, &idle, Status::InProgress, 90)
         };
 
-        let result = Poll::poll(&conn, &poll).await.unwrap();
-        let cmd = Command::current(&conn, rsn).await.unwrap();
-        let cmd_updated = Command::get_by_id(&conn, cmd_task2.command_id)
+        // Check an acknowledged fault is not stored again while it is still reported
+        Poll::poll(&conn, &jammed).await.unwrap();
+        let fault = Fault::get_active(&conn, rsn).await.unwrap().remove(0);
+        fault.acknowledge(&conn).await.unwrap();
+        Poll::poll(&conn, &jammed).await.unwrap();
+        assert!(Fault::get_active(&conn, rsn).await.unwrap().is_empty());
+
+        // Check the fault is stored again once the robot has stopped reporting it
+        Poll::poll(&conn, &poll(rsn, &idle, Status::InProgress, 90))
             .await
             .unwrap();
+        Poll::poll(&conn, &jammed).await.unwrap();
+        let active = Fault::get_active(&conn, rsn).await.unwrap();
+        assert_eq!(1, active.len());
+        assert_ne!(fault.fault_id, active[0].fault_id);
+    }
 
-        // Check the right response
-        assert_eq!(Task(CleaningPattern::ZigZag), result.instruction);
+    #[actix_rt::test]
+    async fn zones_kept_on_new_map() {
+        let rsn = "test_zones_new_map";
+        let conn = setup_tests(rsn).await;
+        let storage = LocalStorage::new(&env::temp_dir().join("sdp-test").to_string_lossy());
+        let metadata = MapMetadata {
+            resolution: 0.05,
+            origin_x: 0.0,
+            origin_y: 0.0,
+        };
+
+        let first = Map::new(&conn, &storage, rsn, "image/png", &metadata, b"first")
+            .await
+            .unwrap();
+        let polygon = vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 1.0, y: 0.0 },
+            Point { x: 1.0, y: 1.0 },
+        ];
+        let no_go = Zone::new(&conn, rsn, first.version, "", &ZoneKind::NoGo, &polygon)
+            .await
+            .unwrap();
 
-        // Check the result was entered into the db
-        assert_eq!(result.clone(), cmd);
+        // Check the no-go zone still applies after the robot uploads a new map
+        let second = Map::new(&conn, &storage, rsn, "image/png", &metadata, b"second")
+            .await
+            .unwrap();
+        assert_eq!(first.version + 1, second.version);
+        assert_eq!(vec![no_go], Zone::get_current(&conn, rsn).await.unwrap());
+    }
 
-        // Check the old commands was marked as complete
-        assert_eq!(false, cmd_updated.completed);
+    #[actix_rt::test]
+    async fn battery_left_out_of_events() {
+        let rsn = "test_battery_events";
+        let conn = setup_tests(rsn).await;
+
+        // Every poll with a new battery level is a battery change
+        let idle = init(&conn, rsn).await;
+        for battery_level in (60..90).rev() {
+            Poll::poll(&conn, &poll(rsn, &idle, Status::InProgress, battery_level))
+                .await
+                .unwrap();
+        }
+        let events = Event::get_by_robot(&conn, rsn).await.unwrap();
+
+        // Check the history only has the other events
+        assert!(!events.is_empty());
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e.event, RobotEvent::Battery(_))));
     }
 
     #[actix_rt::test]
-    async fn set_abort_task_task() {
-        let (_user, _password, rsn, conn) = setup_tests().await;
+    async fn battery_event_only_on_change() {
+        let rsn = "test_battery_change";
+        let conn = setup_tests(rsn).await;
+
+        // The battery level is the same for each poll until the last
+        let idle = init(&conn, rsn).await;
+        for battery_level in &[90, 90, 90, 89] {
+            Poll::poll(&conn, &poll(rsn, &idle, Status::InProgress, *battery_level))
+                .await
+                .unwrap();
+        }
+        let changes: Vec<RobotEvent> = Event::get_since(&conn, rsn, 0)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.event)
+            .filter(|e| matches!(e, RobotEvent::Battery(_)))
+            .collect();
+
+        // Check there is one event when the robot turned on and one when the level dropped
+        assert_eq!(
+            vec![
+                RobotEvent::Battery(BatteryChange {
+                    battery_level: 90,
+                    charging: false,
+                }),
+                RobotEvent::Battery(BatteryChange {
+                    battery_level: 89,
+                    charging: false,
+                }),
+            ],
+            changes
+        );
+    }
 
-        let time_now = chrono::Utc::now();
+    #[actix_rt::test]
+    async fn set_task_obstacle_timeout() {
+        let rsn = "test_obstacle_timeout";
+        let conn = setup_tests(rsn).await;
+
+        // Make the task look like it has been blocked for longer than the timeout
+        let mut paused = paused_task(&conn, rsn).await;
+        paused.paused_at =
+            Some(chrono::Utc::now() - chrono::Duration::seconds(config::obstacle_timeout() + 1));
+        let paused = Command::update(&conn, &paused).await.unwrap();
+
+        let blocked = Poll {
+            obstacle: true,
+            ..poll(rsn, &paused, Status::InProgress, 90)
+        };
+        let result = Poll::poll(&conn, &blocked).await.unwrap();
+        let paused_updated = Command::get_by_id(&conn, paused.command_id).await.unwrap();
 
-        let prev_cmd = Command::new(
-            &conn,
+        // Check the task is cancelled and the robot goes idle
+        assert_eq!(Status::Cancelled, paused_updated.status);
+        assert_eq!(Idle, result.instruction);
+    }
+
+    #[actix_rt::test]
+    async fn offline_recorded_without_poll() {
+        let rsn = "test_offline_sweep";
+        let conn = setup_tests(rsn).await;
+
+        // Make the robot look like it came online then stopped polling
+        let last_seen_at =
+            chrono::Utc::now() - chrono::Duration::seconds(config::robot_offline_threshold() + 10);
+        Event::record(&conn, rsn, last_seen_at, &RobotEvent::Online)
+            .await
+            .unwrap();
+        sqlx::query!(
+            r#"
+UPDATE Robot
+SET last_seen_at = $2
+WHERE robot_serial_number = $1
+        "#,
             rsn,
-            time_now,
-            time_now,
-            &Instruction::Task(CleaningPattern::Circular),
+            last_seen_at
         )
+        .execute(&conn)
         .await
         .unwrap();
 
-        let poll = Poll {
-            robot_serial_number: rsn.to_string(),
-            instruction: Abort(AbortReason::Obstacle),
-            battery_level: 90,
-        };
-
-        let result = Poll::poll(&conn, &poll).await.unwrap();
-        let cmd = Command::current(&conn, rsn).await.unwrap();
-        let prev_cmd_updated = Command::get_by_id(&conn, prev_cmd.command_id)
+        // Check the robot is recorded as offline once, without it being looked at
+        Robot::record_offline(&conn).await.unwrap();
+        Robot::record_offline(&conn).await.unwrap();
+        let offline = Event::get_by_robot(&conn, rsn)
             .await
-            .unwrap();
-
-        // Check the right response
-        assert_eq!(Abort(AbortReason::Obstacle), result.instruction);
-
-        // Check the result was entered into the db
-        assert_eq!(result.clone(), cmd);
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.event == RobotEvent::Offline)
+            .count();
+        assert_eq!(1, offline);
+        assert_eq!(
+            Some(RobotEvent::Offline),
+            Event::last_presence(&conn, rsn).await.unwrap()
+        );
+    }
 
-        // Check the old commands was marked as complete
-        assert_eq!(true, prev_cmd_updated.completed);
+    #[actix_rt::test]
+    async fn patterns_from_capabilities() {
+        let rsn = "test_patterns_capabilities";
+        let conn = setup_tests(rsn).await;
 
-        let time_now = chrono::Utc::now();
+        // Check the robot only offers the patterns it reported, not the defaults
+        let capabilities = vec!["Spot".to_string(), "Mop".to_string()];
+        let robot = Robot::set_capabilities(&conn, rsn, None, None, Some(&capabilities))
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["Spot".to_string()],
+            robot.supported_patterns(&conn).await.unwrap()
+        );
+    }
 
-        let prev_cmd = Command::new(
-            &conn,
-            rsn,
-            time_now,
-            time_now,
-            &Instruction::Task(CleaningPattern::ZigZag),
+    #[actix_rt::test]
+    async fn post_server_only_instruction_rejected() {
+        let rsn = "test_server_only";
+        let conn = setup_tests(rsn).await;
+        let user = User::search_by_username(&conn, &format!("{}_user", rsn))
+            .await
+            .unwrap()
+            .unwrap();
+        let token = Token::new(&user).await.unwrap().token;
+        let mut app = test::init_service(
+            App::new()
+                .data(conn.clone())
+                .service(api::command::create_command),
         )
-        .await
-        .unwrap();
+        .await;
 
-        let poll = Poll {
-            robot_serial_number: rsn.to_string(),
-            instruction: Idle,
-            battery_level: 90,
+        let firmware = FirmwareOffer {
+            release_id: 1,
+            version: "1.0.0".to_string(),
+            checksum: String::new(),
         };
+        for instruction in [ReturnToDock, Instruction::UpdateFirmware(firmware)] {
+            let time_now = chrono::Utc::now().timestamp();
+            let req = test::TestRequest::post()
+                .uri("/command")
+                .header(header::AUTHORIZATION, token.clone())
+                .set_json(&serde_json::json!({
+                    "time_issued": time_now,
+                    "time_instruction": [time_now],
+                    "instruction": instruction,
+                }))
+                .to_request();
+            let res = test::call_service(&mut app, req).await;
+
+            // Check the user can not send the instruction, only the server makes it
+            assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        }
+        assert!(Command::get_all_by_robot_serial_number(&conn, rsn)
+            .await
+            .unwrap()
+            .is_empty());
+    }
 
-        let result = Poll::poll(&conn, &poll).await.unwrap();
-        let cmd = Command::current(&conn, rsn).await.unwrap();
-        let prev_cmd_updated = Command::get_by_id(&conn, prev_cmd.command_id)
+    #[actix_rt::test]
+    async fn old_events_pruned_once_sent() {
+        let rsn = "test_event_prune";
+        let conn = setup_tests(rsn).await;
+        let event_types = vec!["Fault".to_string(), "ObstaclePaused".to_string()];
+        Webhook::new(&conn, rsn, "http://8.8.8.8/hook", &event_types, "secret")
             .await
             .unwrap();
 
-        // Check the right response
-        assert_eq!(Task(CleaningPattern::ZigZag), result.instruction);
-
-        // Check the result was entered into the db
-        assert_eq!(result.clone(), cmd);
+        let old =
+            chrono::Utc::now() - chrono::Duration::seconds(config::event_replay_window() + 60);
+        let record = |time, event| {
+            let conn = conn.clone();
+            async move { Event::record(&conn, rsn, time, &event).await.unwrap() }
+        };
+        let delivered = record(old, RobotEvent::Fault(1)).await;
+        let pending = record(old, RobotEvent::ObstaclePaused(1)).await;
+        let not_sent = record(old, RobotEvent::ObstacleCleared(1)).await;
+        let online = record(old, RobotEvent::Online).await;
+        let recent = record(chrono::Utc::now(), RobotEvent::ObstacleTimeout(1)).await;
+        sqlx::query!(
+            r#"
+UPDATE WebhookDeliveries
+SET status = 'DeliveryStatus::Delivered'
+WHERE event_id = $1
+        "#,
+            delivered.event_id
+        )
+        .execute(&conn)
+        .await
+        .unwrap();
 
-        // Check the old commands was marked as complete
-        assert_eq!(false, prev_cmd_updated.completed);
+        Event::prune(&conn).await.unwrap();
+        let mut remaining: Vec<i64> = Event::get_by_robot(&conn, rsn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.event_id)
+            .collect();
+        remaining.sort_unstable();
+
+        // Check the old events that were sent, or not wanted by a webhook, are removed. The
+        // event waiting to be sent and the last time the robot came online are kept.
+        assert_eq!(
+            vec![pending.event_id, online.event_id, recent.event_id],
+            remaining
+        );
+        assert!(!remaining.contains(&delivered.event_id));
+        assert!(!remaining.contains(&not_sent.event_id));
     }
 }
@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use crate::command::Command;
+    use crate::signing::CommandSigner;
+    use crate::zone::{Point, Zone, ZoneKind};
+
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+    use std::convert::TryInto;
+    use std::env;
+    use std::os::unix::fs::PermissionsExt;
+
+    // Each test has its own key file so the tests can run at the same time
+    fn key_file(name: &str) -> String {
+        let path = env::temp_dir()
+            .join(format!("sdp-test-{}", std::process::id()))
+            .join(name);
+        std::fs::remove_file(&path).ok();
+
+        path.to_string_lossy().to_string()
+    }
+
+    fn command() -> Command {
+        serde_json::from_value(serde_json::json!({
+            "command_id": 7,
+            "robot_serial_number": "serial1",
+            "time_issued": 0,
+            "time_instruction": 0,
+            "instruction": "Idle",
+            "status": "Pending",
+            "lateness": null,
+            "interrupted": false,
+        }))
+        .unwrap()
+    }
+
+    fn no_go_zone() -> Zone {
+        Zone {
+            zone_id: 1,
+            robot_serial_number: "serial1".to_string(),
+            map_version: 1,
+            name: "".to_string(),
+            kind: ZoneKind::NoGo,
+            polygon: vec![
+                Point { x: 0.0, y: 0.0 },
+                Point { x: 1.0, y: 0.0 },
+                Point { x: 1.0, y: 1.0 },
+            ],
+        }
+    }
+
+    #[test]
+    fn sign_verifies_with_public_key() {
+        let signer = CommandSigner::load(&key_file("verify.key")).unwrap();
+        let zones = vec![no_go_zone()];
+        let signed = signer.sign(&command(), &zones).unwrap();
+
+        let public_key = signer.public_key();
+        assert_eq!(public_key.algorithm, "Ed25519");
+        assert_eq!(public_key.key_id, signed.key_id);
+
+        let key_bytes: [u8; 32] = base64::decode(&public_key.public_key)
+            .unwrap()
+            .as_slice()
+            .try_into()
+            .unwrap();
+        let key = VerifyingKey::from_bytes(&key_bytes).unwrap();
+        let signature = Signature::from_slice(&base64::decode(&signed.signature).unwrap()).unwrap();
+        assert!(key.verify(signed.payload.as_bytes(), &signature).is_ok());
+
+        // Changing the payload breaks the signature
+        let changed = signed.payload.replace("serial1", "serial2");
+        assert!(key.verify(changed.as_bytes(), &signature).is_err());
+    }
+
+    #[test]
+    fn sign_includes_zones() {
+        let signer = CommandSigner::load(&key_file("zones.key")).unwrap();
+        let zones = vec![no_go_zone()];
+        let signed = signer.sign(&command(), &zones).unwrap();
+
+        let payload: serde_json::Value = serde_json::from_str(&signed.payload).unwrap();
+        assert_eq!(payload["robot_serial_number"], "serial1");
+        assert_eq!(payload["command_id"], 7);
+        assert_eq!(payload["zones"], serde_json::to_value(&zones).unwrap());
+        assert!(payload["expires_at"].as_i64().unwrap() > chrono::Utc::now().timestamp());
+
+        // The same command with other zones is signed differently
+        let unzoned = signer.sign(&command(), &[]).unwrap();
+        assert_ne!(unzoned.payload, signed.payload);
+        assert_ne!(unzoned.signature, signed.signature);
+    }
+
+    #[test]
+    fn load_keeps_key() {
+        let path = key_file("keep.key");
+        let first = CommandSigner::load(&path).unwrap();
+        let second = CommandSigner::load(&path).unwrap();
+
+        assert_eq!(
+            first.public_key().public_key,
+            second.public_key().public_key
+        );
+
+        // Only the server's user can read the key
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,136 @@
+#[cfg(test)]
+mod tests {
+    use crate::webhook::delivery::{post, sign};
+    use crate::webhook::Webhook;
+    use actix_web::client::{Client, Connector};
+    use actix_web::{test, web, App, HttpRequest, HttpResponse};
+    use std::sync::Arc;
+
+    fn header<'a>(req: &'a HttpRequest, name: &str) -> &'a str {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+    }
+
+    // A receiver that only accepts deliveries signed with "secret"
+    async fn receive(req: HttpRequest, body: String) -> HttpResponse {
+        let timestamp = header(&req, "X-Webhook-Timestamp");
+        let signature = format!("sha256={}", sign("secret", timestamp, &body));
+
+        if header(&req, "X-Webhook-Signature") == signature
+            && header(&req, "X-Webhook-Event") == "Fault"
+        {
+            HttpResponse::Ok().finish()
+        } else {
+            HttpResponse::Unauthorized().finish()
+        }
+    }
+
+    #[test]
+    fn sign_hmac() {
+        // The HMAC-SHA256 of "1610000000.{\"delivery_id\":7}" keyed with "secret"
+        assert_eq!(
+            sign("secret", "1610000000", "{\"delivery_id\":7}"),
+            "942b3ee993776960245ee1ec623d8e96f7a824c72bb683ee75e37602e58c677b"
+        );
+    }
+
+    #[test]
+    fn sign_changes_with_input() {
+        let signature = sign("secret", "1610000000", "{}");
+
+        assert_ne!(sign("other", "1610000000", "{}"), signature);
+        assert_ne!(sign("secret", "1610000001", "{}"), signature);
+        assert_ne!(sign("secret", "1610000000", "{ }"), signature);
+    }
+
+    #[actix_rt::test]
+    async fn webhook_public_url() {
+        assert_eq!(
+            Webhook::check_url("http://8.8.8.8/hook").await.unwrap(),
+            "8.8.8.8:80".parse().unwrap()
+        );
+        assert_eq!(
+            Webhook::check_url("https://[2001:4860:4860::8888]:8443/hook")
+                .await
+                .unwrap(),
+            "[2001:4860:4860::8888]:8443".parse().unwrap()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn webhook_private_url() {
+        for url in &[
+            "http://localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://0.0.0.0/hook",
+            "http://10.0.0.1/hook",
+            "http://172.16.0.1/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://[::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[fe80::1]/hook",
+        ] {
+            assert!(Webhook::check_url(url).await.is_err(), "{}", url);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn webhook_invalid_url() {
+        for url in &["ftp://8.8.8.8/hook", "/hook", "not a url"] {
+            assert!(Webhook::check_url(url).await.is_err(), "{}", url);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn post_https() {
+        // The receiver has a certificate for localhost signed by a test authority
+        let mut server_config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+        let certs = rustls::internal::pemfile::certs(&mut &include_bytes!("tls/localhost.pem")[..])
+            .unwrap();
+        let mut keys = rustls::internal::pemfile::pkcs8_private_keys(
+            &mut &include_bytes!("tls/localhost.key")[..],
+        )
+        .unwrap();
+        server_config
+            .set_single_cert(certs, keys.remove(0))
+            .unwrap();
+        let srv = test::start_with(test::config().rustls(server_config), || {
+            App::new().route("/hook", web::post().to(receive))
+        });
+
+        let webhook = Webhook {
+            webhook_id: 1,
+            robot_serial_number: "test_webhook_https".to_string(),
+            url: format!("https://localhost:{}/hook", srv.addr().port()),
+            event_types: vec!["Fault".to_string()],
+            secret: "secret".to_string(),
+            created_at: chrono::Utc::now(),
+        };
+        let body = "{\"delivery_id\":7}".to_string();
+
+        let mut client_config = rustls::ClientConfig::new();
+        client_config
+            .root_store
+            .add_pem_file(&mut &include_bytes!("tls/ca.pem")[..])
+            .unwrap();
+        let client = Client::builder()
+            .connector(Connector::new().rustls(Arc::new(client_config)).finish())
+            .finish();
+        assert_eq!(
+            post(&client, &webhook, srv.addr(), "Fault", 7, body.clone()).await,
+            Ok(200)
+        );
+
+        // Check the certificate is verified, the test authority is not a public one
+        assert!(
+            post(&Client::default(), &webhook, srv.addr(), "Fault", 7, body)
+                .await
+                .is_err()
+        );
+    }
+}
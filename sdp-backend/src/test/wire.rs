@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod tests {
+    use crate::command::{Command, Instruction};
+    use crate::error::ApiError;
+    use crate::wire::WireFormat;
+
+    use actix_web::http::{header::CONTENT_TYPE, StatusCode};
+
+    const FORMATS: [WireFormat; 3] = [WireFormat::Json, WireFormat::Cbor, WireFormat::MessagePack];
+
+    fn command() -> Command {
+        serde_json::from_str(
+            r#"{"command_id":7,"robot_serial_number":"serial1","time_issued":1600000000,
+                "time_instruction":1600000060,"instruction":{"Task":"Spiral"},
+                "parameters":{"mode":"Vacuum","passes":2},"status":"Pending",
+                "lateness":null,"interrupted":false}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn wire_round_trip() {
+        for format in &FORMATS {
+            let body = format.encode(&command()).unwrap();
+            let decoded: Command = format.decode(&body).unwrap();
+            assert_eq!(command(), decoded);
+
+            let body = format.encode(&Instruction::Idle).unwrap();
+            let decoded: Instruction = format.decode(&body).unwrap();
+            assert_eq!(Instruction::Idle, decoded);
+        }
+    }
+
+    #[test]
+    fn wire_decode_invalid() {
+        for format in &FORMATS {
+            let result: Result<Command, ApiError> = format.decode(b"\xff\x00not a command");
+            assert!(matches!(result, Err(ApiError::InvalidPayload)));
+        }
+    }
+
+    #[test]
+    fn wire_from_mime() {
+        assert_eq!(
+            Some(WireFormat::Json),
+            WireFormat::from_mime("application/json; charset=utf-8")
+        );
+        assert_eq!(
+            Some(WireFormat::Cbor),
+            WireFormat::from_mime("application/cbor")
+        );
+        assert_eq!(
+            Some(WireFormat::MessagePack),
+            WireFormat::from_mime("application/x-msgpack")
+        );
+        assert_eq!(None, WireFormat::from_mime("text/plain"));
+
+        for format in &FORMATS {
+            assert_eq!(Some(*format), WireFormat::from_mime(format.mime()));
+        }
+    }
+
+    #[test]
+    fn wire_error_in_format() {
+        for format in &FORMATS {
+            let res = format.error_response(ApiError::InvalidPayload);
+            assert_eq!(StatusCode::BAD_REQUEST, res.status());
+            assert_eq!(
+                format.mime(),
+                res.headers().get(CONTENT_TYPE).unwrap().to_str().unwrap()
+            );
+        }
+
+        let res = WireFormat::Cbor.error_response(ApiError::AuthenticationFailed);
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+}
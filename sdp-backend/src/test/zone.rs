@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod tests {
+    use crate::zone::{Point, Zone, ZoneKind};
+
+    fn square() -> Vec<Point> {
+        vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 1.0, y: 0.0 },
+            Point { x: 1.0, y: 1.0 },
+            Point { x: 0.0, y: 1.0 },
+        ]
+    }
+
+    #[test]
+    fn zone_valid() {
+        assert!(Zone::validate(&ZoneKind::Room, "Kitchen", &square()).is_ok());
+        assert!(Zone::validate(&ZoneKind::NoGo, "", &square()).is_ok());
+        assert!(Zone::validate(&ZoneKind::CleanOnly, "", &square()[..3]).is_ok());
+    }
+
+    #[test]
+    fn zone_polygon_invalid() {
+        // A polygon needs at least three points
+        assert!(Zone::validate(&ZoneKind::NoGo, "", &square()[..2]).is_err());
+        assert!(Zone::validate(&ZoneKind::NoGo, "", &[]).is_err());
+
+        let mut not_finite = square();
+        not_finite[2].x = f64::NAN;
+        assert!(Zone::validate(&ZoneKind::NoGo, "", &not_finite).is_err());
+
+        let mut infinite = square();
+        infinite[0].y = f64::INFINITY;
+        assert!(Zone::validate(&ZoneKind::NoGo, "", &infinite).is_err());
+    }
+
+    #[test]
+    fn zone_room_needs_name() {
+        assert!(Zone::validate(&ZoneKind::Room, "", &square()).is_err());
+        assert!(Zone::validate(&ZoneKind::Room, "  ", &square()).is_err());
+    }
+}
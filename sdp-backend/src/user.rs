@@ -83,11 +83,11 @@ WHERE U.user_name = $1
     }
 
     pub async fn login(conn: &PgPool, user_name: &str, password: &str) -> Result<Self, ApiError> {
-        let user = Self::search_by_username(&conn, user_name)
+        let user = Self::search_by_username(conn, user_name)
             .await?
             .ok_or(ApiError::LoginFailedUserNotExist)?;
 
-        match verify(&password, &user.password_hash) {
+        match verify(password, &user.password_hash) {
             Ok(verified) if verified => Ok(user),
             _ => Err(ApiError::LoginFailedPasswordIncorrect),
         }
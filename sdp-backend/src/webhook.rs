@@ -0,0 +1,204 @@
+use crate::error::ApiError;
+use crate::event::RobotEvent;
+use actix_web::http::Uri;
+use actix_web::web;
+use chrono::{serde::ts_seconds, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+pub mod delivery;
+
+pub use delivery::{deliver, DeliveryStatus, WebhookDelivery};
+
+// A URL the user wants the events of their robot sent to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Webhook {
+    pub webhook_id: i64,
+    pub robot_serial_number: String,
+    pub url: String,
+    // The kinds of event to send, e.g. "CommandStatus" or "Fault"
+    pub event_types: Vec<String>,
+    // Shared with the receiver to check the deliveries came from us, it is never sent back
+    #[serde(skip)]
+    pub secret: String,
+    #[serde(with = "ts_seconds")]
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+impl Webhook {
+    pub async fn new(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        url: &str,
+        event_types: &[String],
+        secret: &str,
+    ) -> Result<Self, ApiError> {
+        Self::check_url(url).await?;
+        let valid_event_types = !event_types.is_empty()
+            && event_types
+                .iter()
+                .all(|t| RobotEvent::KINDS.contains(&t.as_str()));
+        if !valid_event_types || secret.is_empty() {
+            return Err(ApiError::InvalidWebhook);
+        }
+
+        let created_at = Utc::now();
+        sqlx::query!(
+            r#"
+INSERT INTO Webhooks (robot_serial_number, url, event_types, secret, created_at)
+VALUES ($1, $2, $3, $4, $5)
+RETURNING webhook_id
+               "#,
+            robot_serial_number,
+            url,
+            event_types,
+            secret,
+            created_at
+        )
+        .fetch_one(conn)
+        .await
+        .map(|w| Self {
+            webhook_id: w.webhook_id,
+            robot_serial_number: robot_serial_number.to_string(),
+            url: url.to_string(),
+            event_types: event_types.to_vec(),
+            secret: secret.to_string(),
+            created_at,
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    pub async fn get_by_id(conn: &PgPool, webhook_id: i64) -> Result<Self, ApiError> {
+        sqlx::query!(
+            r#"
+SELECT * FROM Webhooks W
+WHERE W.webhook_id = $1
+               "#,
+            webhook_id
+        )
+        .fetch_one(conn)
+        .await
+        .map(|w| Self {
+            webhook_id: w.webhook_id,
+            robot_serial_number: w.robot_serial_number,
+            url: w.url,
+            event_types: w.event_types,
+            secret: w.secret,
+            created_at: w.created_at,
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    pub async fn get_by_robot(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Vec<Self>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM Webhooks W
+WHERE W.robot_serial_number = $1
+ORDER BY W.webhook_id
+               "#,
+            robot_serial_number
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        let mut webhooks = Vec::new();
+        for w in results {
+            webhooks.push(Self {
+                webhook_id: w.webhook_id,
+                robot_serial_number: w.robot_serial_number,
+                url: w.url,
+                event_types: w.event_types,
+                secret: w.secret,
+                created_at: w.created_at,
+            });
+        }
+
+        Ok(webhooks)
+    }
+
+    // Check the URL is one webhooks can be sent to. The host is looked up each time, as it
+    // can change, and must only have public addresses so webhooks can not be used to reach
+    // the server's own network, e.g. localhost or 169.254.169.254. The address to send to
+    // is returned, so the host is not looked up again and given a different answer.
+    pub async fn check_url(url: &str) -> Result<SocketAddr, ApiError> {
+        let uri = url.parse::<Uri>().map_err(|_| ApiError::InvalidWebhook)?;
+        let port = match (uri.scheme_str(), uri.port_u16()) {
+            (Some("http"), port) => port.unwrap_or(80),
+            (Some("https"), port) => port.unwrap_or(443),
+            _ => return Err(ApiError::InvalidWebhook),
+        };
+        // IPv6 addresses are written in brackets in URLs
+        let host = uri
+            .host()
+            .ok_or(ApiError::InvalidWebhook)?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+
+        let addresses = web::block(move || {
+            (host.as_str(), port)
+                .to_socket_addrs()
+                .map(|a| a.collect::<Vec<_>>())
+        })
+        .await
+        .map_err(|_| ApiError::InvalidWebhook)?;
+
+        match addresses.first() {
+            Some(address) if addresses.iter().all(|a| is_public(&a.ip())) => Ok(*address),
+            _ => Err(ApiError::InvalidWebhook),
+        }
+    }
+
+    // Remove the webhook, along with its delivery history
+    pub async fn delete(&self, conn: &PgPool) -> Result<(), ApiError> {
+        sqlx::query!(
+            r#"
+DELETE FROM Webhooks W
+WHERE W.webhook_id = $1
+               "#,
+            self.webhook_id
+        )
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+}
+
+// Whether the address is on the internet, rather than the server itself or a private network
+fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // 0.0.0.0/8, "this network"
+                || octets[0] == 0
+                // 100.64.0.0/10, shared by carrier-grade NAT
+                || (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(&IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_multicast()
+                    // fc00::/7, unique local addresses
+                    || first & 0xfe00 == 0xfc00
+                    // fe80::/10, link-local addresses
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
@@ -0,0 +1,382 @@
+// Sending events to webhooks. When an event is recorded a delivery is added to the outbox
+// for each webhook that wants it, and a task on the server sends the deliveries that are
+// due, retrying the ones that fail with a growing delay.
+//
+// Each delivery is a POST of the JSON
+//
+//   {"delivery_id": 7, "webhook_id": 2, "event": <the event, as sent on /ws>}
+//
+// with the headers
+//
+//   X-Webhook-Event      the kind of event, e.g. "CommandStatus"
+//   X-Webhook-Delivery   the delivery id, this stays the same when a delivery is retried
+//   X-Webhook-Timestamp  the seconds since the epoch when the delivery was sent
+//   X-Webhook-Signature  "sha256=" then the hex HMAC-SHA256 of the timestamp, a '.' and
+//                        the body, keyed with the webhook secret
+//
+// Any 2xx response means the delivery was received.
+// Deliveries are only sent to public addresses, see Webhook::check_url. For https URLs the
+// receiver's certificate must be signed by one of the usual public certificate authorities.
+
+use crate::config;
+use crate::error::ApiError;
+use crate::event::Event;
+use crate::robot::Robot;
+use crate::webhook::Webhook;
+use actix_web::client::Client;
+use chrono::{
+    serde::{ts_seconds, ts_seconds_option},
+    Duration, Utc,
+};
+use futures::future::join_all;
+use hmac::{Hmac, Mac, NewMac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::postgres::PgPool;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+// How often the outbox is checked for deliveries that are due
+const DELIVERY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+// The most deliveries sent each time the outbox is checked
+const DELIVERY_BATCH: i64 = 20;
+// How long a delivery is held by the server sending it, if the server stops while
+// sending it is sent again after this
+const CLAIM_SECONDS: i64 = 120;
+// The longest wait between attempts, in seconds
+const MAX_RETRY_DELAY: i64 = 3600;
+// How often the events from before the replay window are removed
+const EVENT_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(600);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DeliveryStatus {
+    // Waiting to be sent, or to be retried
+    Pending,
+    Delivered,
+    // Every attempt failed, it will not be tried again
+    Failed,
+}
+
+// An event to send to a webhook, kept after it is sent as the delivery history
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookDelivery {
+    pub delivery_id: i64,
+    pub webhook_id: i64,
+    pub event_id: i64,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    #[serde(with = "ts_seconds")]
+    pub next_attempt_at: chrono::DateTime<Utc>,
+    #[serde(with = "ts_seconds_option")]
+    pub last_attempt_at: Option<chrono::DateTime<Utc>>,
+    // The HTTP status of the last response, None if there was no response
+    pub response_status: Option<i64>,
+    pub last_error: Option<String>,
+    #[serde(with = "ts_seconds")]
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+struct DeliveryBody<'a> {
+    delivery_id: i64,
+    webhook_id: i64,
+    event: &'a Event,
+}
+
+impl WebhookDelivery {
+    // Add a delivery of the event for each of the robots webhooks that want this kind of event
+    pub async fn enqueue(conn: &PgPool, event: &Event) -> Result<(), ApiError> {
+        let time_now = Utc::now();
+
+        sqlx::query!(
+            r#"
+INSERT INTO WebhookDeliveries (webhook_id, event_id, status, next_attempt_at, created_at)
+SELECT W.webhook_id, $1, $2, $3, $3 FROM Webhooks W
+WHERE W.robot_serial_number = $4 AND
+      $5 = ANY(W.event_types)
+               "#,
+            event.event_id,
+            DeliveryStatus::Pending.to_string(),
+            time_now,
+            event.robot_serial_number,
+            event.event.kind()
+        )
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // The most recent deliveries for the webhook, newest first
+    pub async fn get_by_webhook(conn: &PgPool, webhook_id: i64) -> Result<Vec<Self>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM WebhookDeliveries D
+WHERE D.webhook_id = $1
+ORDER BY D.delivery_id DESC
+LIMIT 100
+               "#,
+            webhook_id
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        let mut deliveries = Vec::new();
+        for d in results {
+            deliveries.push(Self {
+                delivery_id: d.delivery_id,
+                webhook_id: d.webhook_id,
+                event_id: d.event_id,
+                status: d.status.into(),
+                attempts: d.attempts,
+                next_attempt_at: d.next_attempt_at,
+                last_attempt_at: d.last_attempt_at,
+                response_status: d.response_status,
+                last_error: d.last_error,
+                created_at: d.created_at,
+            });
+        }
+
+        Ok(deliveries)
+    }
+
+    // Take the deliveries that are due to be sent. They are pushed back so other servers
+    // do not send them at the same time.
+    async fn claim_due(conn: &PgPool) -> Result<Vec<Self>, ApiError> {
+        let time_now = Utc::now();
+
+        let results = sqlx::query!(
+            r#"
+UPDATE WebhookDeliveries D
+SET next_attempt_at = $2
+WHERE D.delivery_id IN (
+      SELECT delivery_id FROM WebhookDeliveries
+      WHERE status = $3 AND
+            next_attempt_at <= $1
+      ORDER BY next_attempt_at
+      LIMIT $4
+      FOR UPDATE SKIP LOCKED)
+RETURNING D.*
+               "#,
+            time_now,
+            time_now + Duration::seconds(CLAIM_SECONDS),
+            DeliveryStatus::Pending.to_string(),
+            DELIVERY_BATCH
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        let mut deliveries = Vec::new();
+        for d in results {
+            deliveries.push(Self {
+                delivery_id: d.delivery_id,
+                webhook_id: d.webhook_id,
+                event_id: d.event_id,
+                status: d.status.into(),
+                attempts: d.attempts,
+                next_attempt_at: d.next_attempt_at,
+                last_attempt_at: d.last_attempt_at,
+                response_status: d.response_status,
+                last_error: d.last_error,
+                created_at: d.created_at,
+            });
+        }
+
+        Ok(deliveries)
+    }
+
+    // Send the delivery, returning the status of the response
+    async fn send(&self, conn: &PgPool, client: &Client) -> Result<u16, String> {
+        let webhook = Webhook::get_by_id(conn, self.webhook_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        // The host may have been changed to a private address since the webhook was added
+        let address = Webhook::check_url(&webhook.url)
+            .await
+            .map_err(|_| "The URL is not a public address".to_string())?;
+        let event = Event::get_by_id(conn, self.event_id)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let body = serde_json::to_string(&DeliveryBody {
+            delivery_id: self.delivery_id,
+            webhook_id: self.webhook_id,
+            event: &event,
+        })
+        .map_err(|e| e.to_string())?;
+
+        post(
+            client,
+            &webhook,
+            address,
+            event.event.kind(),
+            self.delivery_id,
+            body,
+        )
+        .await
+    }
+
+    // Record how the attempt went, working out when to try again if it failed
+    async fn record_attempt(
+        &self,
+        conn: &PgPool,
+        outcome: Result<u16, String>,
+    ) -> Result<(), ApiError> {
+        let time_now = Utc::now();
+        let attempts = self.attempts + 1;
+
+        let (response_status, last_error) = match outcome {
+            Ok(code) if (200..300).contains(&code) => (Some(code as i64), None),
+            Ok(code) => (Some(code as i64), Some(format!("HTTP {}", code))),
+            Err(e) => (None, Some(e)),
+        };
+        let status = if last_error.is_none() {
+            DeliveryStatus::Delivered
+        } else if attempts >= config::webhook_max_attempts() {
+            DeliveryStatus::Failed
+        } else {
+            DeliveryStatus::Pending
+        };
+
+        // Wait twice as long after each failed attempt
+        let next_attempt_at = if status == DeliveryStatus::Pending {
+            let retry_delay = config::webhook_retry_delay() << (attempts - 1).min(20);
+            time_now + Duration::seconds(retry_delay.min(MAX_RETRY_DELAY))
+        } else {
+            time_now
+        };
+
+        sqlx::query!(
+            r#"
+UPDATE WebhookDeliveries
+SET status = $2,
+    attempts = $3,
+    next_attempt_at = $4,
+    last_attempt_at = $5,
+    response_status = $6,
+    last_error = $7
+WHERE delivery_id = $1
+               "#,
+            self.delivery_id,
+            status.to_string(),
+            attempts,
+            next_attempt_at,
+            time_now,
+            response_status,
+            last_error
+        )
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+}
+
+// Keep sending the deliveries in the outbox as they become due. Robots that have
+// stopped polling are also recorded as offline here, so their Offline event is sent,
+// and the old events that have been sent are removed.
+pub async fn deliver(conn: PgPool) {
+    let client = Client::builder()
+        .timeout(std::time::Duration::from_secs(
+            config::webhook_timeout() as u64
+        ))
+        .finish();
+    let mut prune_at = Instant::now();
+
+    loop {
+        match WebhookDelivery::claim_due(&conn).await {
+            // Send the deliveries at the same time, so a few slow receivers do not keep
+            // the rest waiting until after their claim has run out
+            Ok(deliveries) => {
+                let (conn, client) = (&conn, &client);
+                join_all(deliveries.iter().map(|delivery| async move {
+                    let outcome = delivery.send(conn, client).await;
+                    if let Err(e) = delivery.record_attempt(conn, outcome).await {
+                        log::error!("Could not record the webhook delivery {:?}", e);
+                    }
+                }))
+                .await;
+            }
+            Err(e) => log::error!("Could not get the webhook deliveries {:?}", e),
+        }
+
+        if let Err(e) = Robot::record_offline(&conn).await {
+            log::error!("Could not record the offline robots {:?}", e);
+        }
+
+        if Instant::now() >= prune_at {
+            match Event::prune(&conn).await {
+                Ok(()) => prune_at = Instant::now() + EVENT_PRUNE_INTERVAL,
+                Err(e) => log::error!("Could not remove the old events {:?}", e),
+            }
+        }
+
+        actix_rt::time::delay_for(DELIVERY_INTERVAL).await;
+    }
+}
+
+// Post the body of a delivery to the webhook at the address, with the headers described
+// above, returning the status of the response
+pub async fn post(
+    client: &Client,
+    webhook: &Webhook,
+    address: SocketAddr,
+    kind: &str,
+    delivery_id: i64,
+    body: String,
+) -> Result<u16, String> {
+    let timestamp = Utc::now().timestamp().to_string();
+
+    client
+        .post(&webhook.url)
+        .address(address)
+        .header("X-Webhook-Event", kind)
+        .header("X-Webhook-Delivery", delivery_id.to_string())
+        .header("X-Webhook-Timestamp", timestamp.clone())
+        .header(
+            "X-Webhook-Signature",
+            format!("sha256={}", sign(&webhook.secret, &timestamp, &body)),
+        )
+        .content_type("application/json")
+        .send_body(body)
+        .await
+        .map(|res| res.status().as_u16())
+        .map_err(|e| e.to_string())
+}
+
+// The hex HMAC-SHA256 of the timestamp and body, keyed with the webhook secret
+pub fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl std::fmt::Display for DeliveryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeliveryStatus::Pending => write!(f, "DeliveryStatus::Pending"),
+            DeliveryStatus::Delivered => write!(f, "DeliveryStatus::Delivered"),
+            DeliveryStatus::Failed => write!(f, "DeliveryStatus::Failed"),
+        }
+    }
+}
+
+impl From<String> for DeliveryStatus {
+    fn from(status: String) -> Self {
+        match &status[..] {
+            "DeliveryStatus::Delivered" => DeliveryStatus::Delivered,
+            "DeliveryStatus::Failed" => DeliveryStatus::Failed,
+            _ => DeliveryStatus::Pending,
+        }
+    }
+}
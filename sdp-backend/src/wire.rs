@@ -0,0 +1,125 @@
+use crate::error::ApiError;
+use actix_web::http::header::{ACCEPT, CONTENT_TYPE};
+use actix_web::{dev, web::Bytes, FromRequest, HttpRequest, HttpResponse};
+use futures::Future;
+use serde::{de::DeserializeOwned, Serialize};
+use std::pin::Pin;
+
+// The formats robots can send and receive messages in. JSON is used unless the robot
+// asks for another format, with the Content-Type of its request for the request body
+// and the Accept header for the response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WireFormat {
+    Json,
+    Cbor,
+    MessagePack,
+}
+
+impl WireFormat {
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        // Ignore any parameters, e.g. "application/json; charset=utf-8"
+        match mime.split(';').next()?.trim() {
+            "application/json" => Some(WireFormat::Json),
+            "application/cbor" => Some(WireFormat::Cbor),
+            "application/msgpack" | "application/x-msgpack" => Some(WireFormat::MessagePack),
+            _ => None,
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            WireFormat::Json => "application/json",
+            WireFormat::Cbor => "application/cbor",
+            WireFormat::MessagePack => "application/msgpack",
+        }
+    }
+
+    // The format of the request body, from its Content-Type
+    pub fn of_request(req: &HttpRequest) -> Result<Self, ApiError> {
+        match req.headers().get(CONTENT_TYPE) {
+            Some(content_type) => content_type
+                .to_str()
+                .ok()
+                .and_then(Self::from_mime)
+                .ok_or(ApiError::UnsupportedFormat),
+            None => Ok(WireFormat::Json),
+        }
+    }
+
+    // The format to respond in, the first format in the Accept header that is supported
+    pub fn accepted(req: &HttpRequest) -> Self {
+        req.headers()
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .and_then(|accept| accept.split(',').find_map(Self::from_mime))
+            .unwrap_or(WireFormat::Json)
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, body: &[u8]) -> Result<T, ApiError> {
+        match self {
+            WireFormat::Json => serde_json::from_slice(body).map_err(|_| ApiError::InvalidPayload),
+            WireFormat::Cbor => serde_cbor::from_slice(body).map_err(|_| ApiError::InvalidPayload),
+            WireFormat::MessagePack => {
+                rmp_serde::from_slice(body).map_err(|_| ApiError::InvalidPayload)
+            }
+        }
+    }
+
+    // The error in this format, with the same status as when it is sent as JSON
+    pub fn error_response(&self, error: ApiError) -> HttpResponse {
+        if *self == WireFormat::Json {
+            return error.into();
+        }
+
+        let body = match self.encode(&error.to_string()) {
+            Ok(b) => b,
+            Err(e) => return e.into(),
+        };
+        let status = HttpResponse::from(error).status();
+
+        HttpResponse::build(status)
+            .content_type(self.mime())
+            .body(body)
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        match self {
+            WireFormat::Json => serde_json::to_vec(value).map_err(|_| ApiError::SerializationError),
+            WireFormat::Cbor => serde_cbor::to_vec(value).map_err(|_| ApiError::SerializationError),
+            // Structs are sent as maps with the field names, the same as the other formats
+            WireFormat::MessagePack => {
+                rmp_serde::to_vec_named(value).map_err(|_| ApiError::SerializationError)
+            }
+        }
+    }
+}
+
+// A request body in any of the wire formats, read according to its Content-Type
+pub struct WireBody<T>(pub T);
+
+impl<T> std::ops::Deref for WireBody<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for WireBody<T> {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, ApiError>>>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let format = match WireFormat::of_request(req) {
+            Ok(f) => f,
+            Err(e) => return Box::pin(async { Err(e) }),
+        };
+        let body = Bytes::from_request(req, payload);
+
+        Box::pin(async move {
+            let body = body.await.map_err(|_| ApiError::InvalidPayload)?;
+            format.decode(&body).map(WireBody)
+        })
+    }
+}
@@ -0,0 +1,227 @@
+use crate::command::{Command, TaskParameters};
+use crate::error::ApiError;
+use crate::map::Map;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ZoneKind {
+    // A named room that tasks can target
+    Room,
+    // An area the robot must never enter
+    NoGo,
+    // An area the robot should only clean, not travel through to get elsewhere
+    CleanOnly,
+}
+
+// A point in map coordinates, in meters
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+// An area drawn on a version of the robots map
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub zone_id: i64,
+    pub robot_serial_number: String,
+    pub map_version: i64,
+    pub name: String,
+    pub kind: ZoneKind,
+    pub polygon: Vec<Point>,
+}
+
+impl Zone {
+    // Check the zone can be stored, the polygon needs at least three points
+    pub fn validate(kind: &ZoneKind, name: &str, polygon: &[Point]) -> Result<(), ApiError> {
+        let valid_polygon =
+            polygon.len() >= 3 && polygon.iter().all(|p| p.x.is_finite() && p.y.is_finite());
+        // Rooms are chosen by name in the app, so they must have one
+        let valid_name = kind != &ZoneKind::Room || !name.trim().is_empty();
+
+        if valid_polygon && valid_name {
+            Ok(())
+        } else {
+            Err(ApiError::InvalidZone)
+        }
+    }
+
+    pub async fn new(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        map_version: i64,
+        name: &str,
+        kind: &ZoneKind,
+        polygon: &[Point],
+    ) -> Result<Self, ApiError> {
+        Self::validate(kind, name, polygon)?;
+
+        // The zone has to be drawn on a map the robot has uploaded
+        Map::get_by_version(conn, robot_serial_number, map_version).await?;
+
+        let polygon_json =
+            serde_json::to_string(polygon).map_err(|_| ApiError::SerializationError)?;
+        sqlx::query!(
+            r#"
+INSERT INTO Zones (robot_serial_number, map_version, name, kind, polygon)
+VALUES ($1, $2, $3, $4, $5)
+RETURNING zone_id
+               "#,
+            robot_serial_number,
+            map_version,
+            name,
+            kind.to_string(),
+            polygon_json
+        )
+        .fetch_one(conn)
+        .await
+        .map(|z| Self {
+            zone_id: z.zone_id,
+            robot_serial_number: robot_serial_number.to_string(),
+            map_version,
+            name: name.to_string(),
+            kind: kind.clone(),
+            polygon: polygon.to_vec(),
+        })
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    pub async fn get_by_id(conn: &PgPool, zone_id: i64) -> Result<Self, ApiError> {
+        let z = sqlx::query!(
+            r#"
+SELECT * FROM Zones Z
+WHERE Z.zone_id = $1
+               "#,
+            zone_id
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        Ok(Self {
+            zone_id: z.zone_id,
+            robot_serial_number: z.robot_serial_number,
+            map_version: z.map_version,
+            name: z.name,
+            kind: z.kind.into(),
+            polygon: Self::parse_polygon(&z.polygon)?,
+        })
+    }
+
+    // The zones on the newest version of the robots map that has zones. Zones are only
+    // moved to a new map when they are drawn on it again, so the robot keeps avoiding
+    // the no-go zones from the older map until then.
+    pub async fn get_current(
+        conn: &PgPool,
+        robot_serial_number: &str,
+    ) -> Result<Vec<Self>, ApiError> {
+        let results = sqlx::query!(
+            r#"
+SELECT * FROM Zones Z
+WHERE Z.robot_serial_number = $1 AND
+      Z.map_version = (SELECT MAX(L.map_version) FROM Zones L WHERE L.robot_serial_number = $1)
+ORDER BY Z.zone_id
+               "#,
+            robot_serial_number
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|_| ApiError::DatabaseConnFailed)?;
+
+        let mut zones = Vec::new();
+        for z in results {
+            zones.push(Self {
+                zone_id: z.zone_id,
+                robot_serial_number: z.robot_serial_number,
+                map_version: z.map_version,
+                name: z.name,
+                kind: z.kind.into(),
+                polygon: Self::parse_polygon(&z.polygon)?,
+            });
+        }
+
+        Ok(zones)
+    }
+
+    // A stored polygon that can not be read is an error, rather than a zone with no
+    // area that would let the robot into a no-go zone
+    fn parse_polygon(polygon: &str) -> Result<Vec<Point>, ApiError> {
+        serde_json::from_str(polygon).map_err(|_| ApiError::SerializationError)
+    }
+
+    pub async fn delete(&self, conn: &PgPool) -> Result<(), ApiError> {
+        sqlx::query!(
+            r#"
+DELETE FROM Zones
+WHERE zone_id = $1
+               "#,
+            self.zone_id
+        )
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(|_| ApiError::DatabaseConnFailed)
+    }
+
+    // Check the rooms a task targets are rooms on the robots current map
+    pub async fn check_rooms(
+        conn: &PgPool,
+        robot_serial_number: &str,
+        parameters: &Option<TaskParameters>,
+    ) -> Result<(), ApiError> {
+        let rooms = match parameters.as_ref().and_then(|p| p.rooms.as_ref()) {
+            Some(rooms) => rooms,
+            None => return Ok(()),
+        };
+
+        let zones = Self::get_current(conn, robot_serial_number).await?;
+        let is_room = |id: &i64| {
+            zones
+                .iter()
+                .any(|z| &z.zone_id == id && z.kind == ZoneKind::Room)
+        };
+
+        if rooms.iter().all(is_room) {
+            Ok(())
+        } else {
+            Err(ApiError::InvalidZone)
+        }
+    }
+
+    // The zones the robot needs for the command, no-go and clean-only zones
+    // apply to everything and rooms are sent when a task targets them
+    pub async fn for_command(conn: &PgPool, command: &Command) -> Result<Vec<Self>, ApiError> {
+        let rooms = command
+            .parameters
+            .as_ref()
+            .and_then(|p| p.rooms.clone())
+            .unwrap_or_default();
+
+        Ok(Self::get_current(conn, &command.robot_serial_number)
+            .await?
+            .into_iter()
+            .filter(|z| z.kind != ZoneKind::Room || rooms.contains(&z.zone_id))
+            .collect())
+    }
+}
+
+impl std::fmt::Display for ZoneKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZoneKind::Room => write!(f, "ZoneKind::Room"),
+            ZoneKind::NoGo => write!(f, "ZoneKind::NoGo"),
+            ZoneKind::CleanOnly => write!(f, "ZoneKind::CleanOnly"),
+        }
+    }
+}
+
+impl From<String> for ZoneKind {
+    fn from(kind: String) -> Self {
+        match &kind[..] {
+            "ZoneKind::Room" => ZoneKind::Room,
+            "ZoneKind::CleanOnly" => ZoneKind::CleanOnly,
+            _ => ZoneKind::NoGo,
+        }
+    }
+}